@@ -8,16 +8,46 @@ extern crate slog;
 
 use slog::*;
 use std::result;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// State of `Failover` circuit breaker
+struct Circuit {
+    /// Consecutive failures of the primary drain
+    failures: usize,
+    /// When did logging switch to the secondary drain, if it did
+    open_since: Option<Instant>,
+    /// Is a record being used to probe the primary drain
+    probing: bool,
+}
+
+/// Where should a record go
+enum Route {
+    Primary,
+    Probe,
+    Secondary,
+}
 
 /// `Drain` that switches destination of error
 ///
 /// Logs everything to drain `D1`, but in case of it reporting an error,
 /// switching to `D2`. If `D2` returns an error too, `Failover` will return
 /// an error.
+///
+/// Works as a circuit breaker: after `failure_threshold` consecutive errors
+/// of `D1`, records are passed straight to `D2`, without trying `D1` first.
+/// After `backoff`, next record is used to probe `D1` again: if it succeeds,
+/// logging switches back to `D1`, otherwise `D2` is used for another
+/// `backoff`.
+///
+/// Every switch is reported by logging an additional record to `D2`.
 pub struct Failover<D1: Drain, D2: Drain>
 {
     drain1: D1,
     drain2: D2,
+    failure_threshold: usize,
+    backoff: Duration,
+    circuit: Mutex<Circuit>,
 }
 
 impl<D1: Drain, D2: Drain, O, E2> Failover<D1, D2>
@@ -26,10 +56,72 @@ impl<D1: Drain, D2: Drain, O, E2> Failover<D1, D2>
     D2 : Drain<Err = E2, Ok = O>,
 {
     /// Create `Failover`
+    ///
+    /// By default switches to `D2` after 3 consecutive errors, and probes
+    /// `D1` again after 10 seconds.
     pub fn new(drain1: D1, drain2: D2) -> Self {
         Failover {
             drain1: drain1,
             drain2: drain2,
+            failure_threshold: 3,
+            backoff: Duration::from_secs(10),
+            circuit: Mutex::new(Circuit {
+                failures: 0,
+                open_since: None,
+                probing: false,
+            }),
+        }
+    }
+
+    /// Set number of consecutive errors of `D1` switching to `D2`
+    pub fn failure_threshold(mut self, failures: usize) -> Self {
+        self.failure_threshold = failures;
+        self
+    }
+
+    /// Set how long to use `D2` before probing `D1` again
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    fn route(&self) -> Route {
+        let mut circuit = match self.circuit.lock() {
+            Ok(circuit) => circuit,
+            Err(_) => return Route::Primary,
+        };
+        match circuit.open_since {
+            None => Route::Primary,
+            Some(since) => {
+                if !circuit.probing && since.elapsed() >= self.backoff {
+                    circuit.probing = true;
+                    Route::Probe
+                } else {
+                    Route::Secondary
+                }
+            }
+        }
+    }
+
+    /// Update state after `D1` was tried; returns whether state changed,
+    /// and number of consecutive failures
+    fn update(&self, success: bool) -> (bool, usize) {
+        let mut circuit = match self.circuit.lock() {
+            Ok(circuit) => circuit,
+            Err(_) => return (false, 0),
+        };
+        let was_open = circuit.open_since.is_some();
+        circuit.probing = false;
+        if success {
+            circuit.failures = 0;
+            circuit.open_since = None;
+            (was_open, 0)
+        } else {
+            circuit.failures += 1;
+            if was_open || circuit.failures >= self.failure_threshold {
+                circuit.open_since = Some(Instant::now());
+            }
+            (!was_open && circuit.open_since.is_some(), circuit.failures)
         }
     }
 }
@@ -45,11 +137,54 @@ impl<D1, D2, E2, O> Drain for Failover<D1, D2>
            info: &Record,
            logger_values: &OwnedKVList)
            -> result::Result<Self::Ok, Self::Err> {
+        if let Route::Secondary = self.route() {
+            return self.drain2.log(info, logger_values);
+        }
         match self.drain1.log(info, logger_values) {
-            Ok(ok) => Ok(ok),
-            Err(_) => self.drain2.log(info, logger_values),
+            Ok(ok) => {
+                if let (true, _) = self.update(true) {
+                    let _ = self.drain2.log(
+                        &record!(
+                            Level::Info,
+                            "",
+                            &format_args!("primary drain recovered"),
+                            b!()
+                        ),
+                        logger_values,
+                    );
+                }
+                Ok(ok)
+            }
+            Err(_) => {
+                if let (true, failures) = self.update(false) {
+                    let _ = self.drain2.log(
+                        &record!(
+                            Level::Warning,
+                            "",
+                            &format_args!(
+                                "primary drain failed, switching to secondary"
+                            ),
+                            b!("failures" => failures)
+                        ),
+                        logger_values,
+                    );
+                }
+                self.drain2.log(info, logger_values)
+            }
         }
     }
+
+    fn flush(&self) -> slog::Result {
+        let res1 = self.drain1.flush();
+        let res2 = self.drain2.flush();
+        res1.and(res2)
+    }
+
+    fn shutdown(&self) -> slog::Result {
+        let res1 = self.drain1.shutdown();
+        let res2 = self.drain2.shutdown();
+        res1.and(res2)
+    }
 }
 
 /// Failover logging to secondary drain on primary's failure
@@ -62,3 +197,96 @@ pub fn failover<D1: Drain, D2: Drain, O, E2>(d1: D1, d2: D2) -> Failover<D1, D2>
 {
     Failover::new(d1, d2)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// Drain recording messages, failing on command
+    #[derive(Clone)]
+    struct Switchable {
+        messages: Arc<Mutex<Vec<String>>>,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl Switchable {
+        fn new() -> Self {
+            Switchable {
+                messages: Arc::new(Mutex::new(vec![])),
+                fail: Arc::new(AtomicBool::new(false)),
+            }
+        }
+
+        fn fail(&self, fail: bool) {
+            self.fail.store(fail, Ordering::SeqCst)
+        }
+
+        fn messages(&self) -> Vec<String> {
+            self.messages.lock().unwrap().clone()
+        }
+    }
+
+    impl Drain for Switchable {
+        type Ok = ();
+        type Err = ();
+        fn log(&self,
+               info: &Record,
+               _: &OwnedKVList)
+               -> result::Result<(), ()> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(());
+            }
+            self.messages.lock().unwrap().push(format!("{}", info.msg()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failover_circuit_breaker() {
+        let (primary, secondary) = (Switchable::new(), Switchable::new());
+        let drain = Failover::new(primary.clone(), secondary.clone())
+            .failure_threshold(2)
+            .backoff(Duration::from_millis(50));
+        let log = Logger::root(drain.fuse(), o!());
+
+        info!(log, "1");
+        primary.fail(true);
+        info!(log, "2");
+        info!(log, "3");
+        primary.fail(false);
+        // circuit is open, primary is not tried
+        info!(log, "4");
+        thread::sleep(Duration::from_millis(60));
+        info!(log, "5");
+        info!(log, "6");
+
+        assert_eq!(primary.messages(), ["1", "5", "6"]);
+        assert_eq!(secondary.messages(),
+                   ["2",
+                    "primary drain failed, switching to secondary",
+                    "3",
+                    "4",
+                    "primary drain recovered"]);
+
+        // failed probe keeps the circuit open for another backoff
+        primary.fail(true);
+        info!(log, "7");
+        info!(log, "8");
+        primary.fail(false);
+        thread::sleep(Duration::from_millis(60));
+        primary.fail(true);
+        info!(log, "9");
+        primary.fail(false);
+        info!(log, "10");
+        assert_eq!(primary.messages(), ["1", "5", "6"]);
+        assert_eq!(&secondary.messages()[5..],
+                   ["7",
+                    "primary drain failed, switching to secondary",
+                    "8",
+                    "9",
+                    "10"]);
+    }
+}
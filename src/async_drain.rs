@@ -0,0 +1,422 @@
+use core::{cmp, fmt, result};
+use std::collections::VecDeque;
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+
+use owned::OwnedRecord;
+use {Drain, Error, Level, Never, OwnedKVList, Record, Result};
+
+/// What `Async` does with a record when its channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the logging thread until there is room in the channel
+    Block,
+    /// Drop the new record
+    DropNewest,
+    /// Drop the oldest record waiting in the channel, to make room for the
+    /// new one
+    DropOldest,
+    /// Drop the new record if its level is below given level; block for
+    /// more important ones
+    DropBelow(Level),
+}
+
+/// Error returned by `Async`
+#[derive(Debug)]
+pub enum AsyncError {
+    /// Serializing record key-value pairs into owned values failed
+    Serialize(Error),
+    /// The worker thread is gone (after `Drain::shutdown`, or because the
+    /// wrapped drain panicked)
+    Disconnected,
+}
+
+impl fmt::Display for AsyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AsyncError::Serialize(ref e) => {
+                write!(f, "failed to serialize record: {}", e)
+            }
+            AsyncError::Disconnected => write!(f, "async worker is gone"),
+        }
+    }
+}
+
+impl ::std::error::Error for AsyncError {
+    fn description(&self) -> &str {
+        match *self {
+            AsyncError::Serialize(_) => "failed to serialize record",
+            AsyncError::Disconnected => "async worker is gone",
+        }
+    }
+}
+
+/// Message sent to the worker thread
+enum Msg {
+    Record(OwnedRecord),
+    /// Flush the wrapped drain, and report it with given ticket
+    Flush(u64),
+}
+
+struct Queue {
+    msgs: VecDeque<Msg>,
+    /// Number of `Msg::Record`s in `msgs`
+    records: usize,
+    closed: bool,
+    /// Call `Drain::shutdown` instead of `Drain::flush` on exit
+    shutdown: bool,
+    /// Worker thread is gone
+    exited: bool,
+    flush_requested: u64,
+    flush_done: u64,
+    /// First error returned by `flush` or `shutdown` of the wrapped drain
+    /// not yet reported
+    error: Option<Error>,
+}
+
+/// State shared between `Async` and its worker thread
+struct Shared {
+    queue: Mutex<Queue>,
+    /// Signaled when a message is added, or the channel is closed
+    not_empty: Condvar,
+    /// Signaled when a record is taken by the worker
+    not_full: Condvar,
+    /// Signaled when a flush is done, or the worker exits
+    flushed: Condvar,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    dropped: AtomicUsize,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<Queue> {
+        // the lock is never held while calling the wrapped drain, so
+        // poisoning is not possible
+        match self.queue.lock() {
+            Ok(queue) => queue,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn wait<'a>(
+        &self,
+        cond: &Condvar,
+        queue: MutexGuard<'a, Queue>,
+    ) -> MutexGuard<'a, Queue> {
+        match cond.wait(queue) {
+            Ok(queue) => queue,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn push(&self, record: OwnedRecord) -> result::Result<(), AsyncError> {
+        let mut queue = self.lock();
+        while queue.records >= self.capacity && !queue.closed {
+            let drop_newest = match self.overflow {
+                OverflowPolicy::Block => false,
+                OverflowPolicy::DropNewest => true,
+                OverflowPolicy::DropOldest => {
+                    let oldest = queue.msgs.iter().position(|msg| match *msg {
+                        Msg::Record(_) => true,
+                        Msg::Flush(_) => false,
+                    });
+                    if let Some(oldest) = oldest {
+                        queue.msgs.remove(oldest);
+                        queue.records -= 1;
+                    }
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+                OverflowPolicy::DropBelow(level) => {
+                    !record.level().is_at_least(level)
+                }
+            };
+            if drop_newest {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            queue = self.wait(&self.not_full, queue);
+        }
+        if queue.closed {
+            return Err(AsyncError::Disconnected);
+        }
+        queue.msgs.push_back(Msg::Record(record));
+        queue.records += 1;
+        self.not_empty.notify_one();
+        Ok(())
+    }
+
+    /// Take next message; returns `None` once the channel is closed and
+    /// empty
+    fn pop(&self) -> Option<Msg> {
+        let mut queue = self.lock();
+        loop {
+            if let Some(msg) = queue.msgs.pop_front() {
+                if let Msg::Record(_) = msg {
+                    queue.records -= 1;
+                    self.not_full.notify_one();
+                }
+                return Some(msg);
+            }
+            if queue.closed {
+                return None;
+            }
+            queue = self.wait(&self.not_empty, queue);
+        }
+    }
+
+    /// Wait until all the records sent so far are passed to the wrapped
+    /// drain, and it's flushed
+    fn flush(&self) -> Result {
+        let mut queue = self.lock();
+        if queue.closed {
+            return Err(Error::Other);
+        }
+        queue.flush_requested += 1;
+        let ticket = queue.flush_requested;
+        queue.msgs.push_back(Msg::Flush(ticket));
+        self.not_empty.notify_one();
+        while queue.flush_done < ticket {
+            if queue.exited {
+                return Err(Error::Other);
+            }
+            queue = self.wait(&self.flushed, queue);
+        }
+        match queue.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_done(&self, ticket: u64, res: Result) {
+        let mut queue = self.lock();
+        queue.flush_done = ticket;
+        if let Err(e) = res {
+            if queue.error.is_none() {
+                queue.error = Some(e);
+            }
+        }
+        self.flushed.notify_all();
+    }
+
+    fn close(&self, shutdown: bool) {
+        let mut queue = self.lock();
+        queue.closed = true;
+        queue.shutdown |= shutdown;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+    }
+}
+
+/// Closes the channel when the worker thread exits, even by panicking, so
+/// logging threads don't block forever
+struct CloseOnExit(Arc<Shared>);
+
+impl Drop for CloseOnExit {
+    fn drop(&mut self) {
+        self.0.close(false);
+        self.0.lock().exited = true;
+        self.0.flushed.notify_all();
+    }
+}
+
+/// `Async` builder
+pub struct AsyncBuilder<D> {
+    drain: D,
+    chan_size: usize,
+    overflow: OverflowPolicy,
+    thread_name: Option<String>,
+}
+
+impl<D> AsyncBuilder<D>
+where
+    D: Drain<Ok = (), Err = Never> + Send + 'static,
+{
+    /// Set channel size (default: 128)
+    pub fn chan_size(mut self, size: usize) -> Self {
+        self.chan_size = size;
+        self
+    }
+
+    /// Set what to do when the channel is full (default:
+    /// `OverflowPolicy::Block`)
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set name of the worker thread
+    pub fn thread_name(mut self, name: String) -> Self {
+        self.thread_name = Some(name);
+        self
+    }
+
+    /// Start the worker thread, and create `Async`
+    pub fn build(self) -> Async {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                msgs: VecDeque::with_capacity(self.chan_size),
+                records: 0,
+                closed: false,
+                shutdown: false,
+                exited: false,
+                flush_requested: 0,
+                flush_done: 0,
+                error: None,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            flushed: Condvar::new(),
+            capacity: cmp::max(self.chan_size, 1),
+            overflow: self.overflow,
+            dropped: AtomicUsize::new(0),
+        });
+
+        let mut builder = thread::Builder::new();
+        if let Some(name) = self.thread_name {
+            builder = builder.name(name);
+        }
+        let worker_shared = shared.clone();
+        let drain = self.drain;
+        let join = builder
+            .spawn(move || {
+                let _close = CloseOnExit(worker_shared.clone());
+                while let Some(msg) = worker_shared.pop() {
+                    match msg {
+                        Msg::Record(record) => {
+                            let _ = record.log_to(&drain);
+                        }
+                        Msg::Flush(ticket) => {
+                            worker_shared.flush_done(ticket, drain.flush())
+                        }
+                    }
+                }
+                let shutdown = worker_shared.lock().shutdown;
+                let res = if shutdown {
+                    drain.shutdown()
+                } else {
+                    drain.flush()
+                };
+                if let Err(e) = res {
+                    let mut queue = worker_shared.lock();
+                    if queue.error.is_none() {
+                        queue.error = Some(e);
+                    }
+                }
+            })
+            .expect("failed to spawn async logging thread");
+
+        Async {
+            shared: shared,
+            join: Mutex::new(Some(join)),
+        }
+    }
+}
+
+/// `Drain` passing records to another drain running in a worker thread
+///
+/// Moves formatting and I/O off the logging thread: every record is
+/// converted into an `OwnedRecord` (logger values are kept as a cheaply
+/// cloned `OwnedKVList`) and sent through a bounded channel to the worker
+/// thread, which passes it to the wrapped drain.
+///
+/// What happens when the channel is full is controlled by
+/// `OverflowPolicy`. The number of records dropped because of it is
+/// available through `Async::dropped`.
+///
+/// On drop, `Async` waits for the worker thread to pass all the records
+/// still in the channel, and flushes the wrapped drain. `Drain::flush` waits
+/// for records logged so far, without closing the channel.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = Async::new(Discard)
+///     .chan_size(1024)
+///     .overflow(OverflowPolicy::DropBelow(Level::Warning))
+///     .build();
+/// # let _ = drain;
+/// ```
+pub struct Async {
+    shared: Arc<Shared>,
+    // `Mutex` keeps `Async` `RefUnwindSafe`, as required by `Logger`
+    join: Mutex<Option<thread::JoinHandle<()>>>,
+}
+
+impl Async {
+    /// Create `AsyncBuilder` for `Async` wrapping `drain`
+    ///
+    /// Make sure to call `AsyncBuilder::build`.
+    #[cfg_attr(clippy, allow(renamed_and_removed_lints, new_ret_no_self))]
+    pub fn new<D>(drain: D) -> AsyncBuilder<D>
+    where
+        D: Drain<Ok = (), Err = Never> + Send + 'static,
+    {
+        AsyncBuilder {
+            drain: drain,
+            chan_size: 128,
+            overflow: OverflowPolicy::Block,
+            thread_name: None,
+        }
+    }
+
+    /// Number of records dropped due to full channel so far
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the worker thread to exit
+    fn join(&self) -> Result {
+        let join = match self.join.lock() {
+            Ok(mut join) => join.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        match join {
+            Some(join) => join.join().map_err(|_| Error::Other),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drain for Async {
+    type Ok = ();
+    type Err = AsyncError;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let record = OwnedRecord::new(record, logger_values)
+            .map_err(AsyncError::Serialize)?;
+        self.shared.push(record)
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    /// Wait until all the records logged so far are passed to the wrapped
+    /// drain, and flush it
+    fn flush(&self) -> Result {
+        self.shared.flush()
+    }
+    /// Close the channel, wait for the worker thread to pass all the
+    /// records still in it, and shut down the wrapped drain
+    ///
+    /// Records logged afterwards return `AsyncError::Disconnected`.
+    fn shutdown(&self) -> Result {
+        self.shared.close(true);
+        self.join()?;
+        match self.shared.lock().error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Async {
+    fn drop(&mut self) {
+        self.shared.close(false);
+        let _ = self.join();
+    }
+}
@@ -0,0 +1,137 @@
+use core::result;
+use std::sync::{Arc, RwLock};
+
+use {
+    Drain, Level, Never, OwnedKVList, Record, Result,
+    SendSyncRefUnwindSafeDrain,
+};
+
+type ErasedDrain<O, E> = Arc<SendSyncRefUnwindSafeDrain<Ok = O, Err = E>>;
+
+/// `Drain` that can be replaced at runtime
+///
+/// `AtomicSwitch` forwards all records to a current drain, which can be
+/// replaced at any time using an `AtomicSwitchCtrl` handle (see
+/// `AtomicSwitch::ctrl`), eg. to redirect logging after configuration reload
+/// or daemonization, without rebuilding the `Logger` hierarchy.
+///
+/// The current drain is kept as a type-erased
+/// `Arc<SendSyncRefUnwindSafeDrain>`. Every `Drain::log` call clones that
+/// `Arc` before using it, so records being logged during a switch finish on
+/// the old drain, which is dropped after the last of them completes.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::*;
+///
+/// fn main() {
+///     let switch = AtomicSwitch::new(Discard);
+///     let ctrl = switch.ctrl();
+///
+///     let log = Logger::root(switch, o!());
+///     info!(log, "goes to the first drain");
+///
+///     ctrl.set(Discard.filter_level(Level::Info).ignore_res());
+///     info!(log, "goes to the second drain");
+/// }
+/// ```
+pub struct AtomicSwitch<O = (), E = Never>(Arc<RwLock<ErasedDrain<O, E>>>);
+
+impl<O, E> AtomicSwitch<O, E> {
+    /// Create `AtomicSwitch` forwarding to `drain`
+    pub fn new<D>(drain: D) -> Self
+    where
+        D: SendSyncRefUnwindSafeDrain<Ok = O, Err = E> + 'static,
+    {
+        AtomicSwitch::new_from_arc(Arc::new(drain))
+    }
+
+    /// Create `AtomicSwitch` forwarding to an already type-erased `drain`
+    pub fn new_from_arc(drain: ErasedDrain<O, E>) -> Self {
+        AtomicSwitch(Arc::new(RwLock::new(drain)))
+    }
+
+    /// Get a handle to control this `AtomicSwitch`
+    pub fn ctrl(&self) -> AtomicSwitchCtrl<O, E> {
+        AtomicSwitchCtrl(self.0.clone())
+    }
+
+    fn current(&self) -> ErasedDrain<O, E> {
+        match self.0.read() {
+            Ok(drain) => drain.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+impl<O, E> Clone for AtomicSwitch<O, E> {
+    fn clone(&self) -> Self {
+        AtomicSwitch(self.0.clone())
+    }
+}
+
+impl<O, E> Drain for AtomicSwitch<O, E> {
+    type Ok = O;
+    type Err = E;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        self.current().log(record, logger_values)
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.current().is_enabled(level)
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.current().flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.current().shutdown()
+    }
+}
+
+/// Handle replacing the drain of an `AtomicSwitch`
+///
+/// It can be cloned and sent between threads freely.
+pub struct AtomicSwitchCtrl<O = (), E = Never>(Arc<RwLock<ErasedDrain<O, E>>>);
+
+impl<O, E> AtomicSwitchCtrl<O, E> {
+    /// Replace the current drain with `drain`
+    pub fn set<D>(&self, drain: D)
+    where
+        D: SendSyncRefUnwindSafeDrain<Ok = O, Err = E> + 'static,
+    {
+        let _ = self.swap(Arc::new(drain));
+    }
+
+    /// Replace the current drain with `drain`, returning the old one
+    pub fn swap(&self, drain: ErasedDrain<O, E>) -> ErasedDrain<O, E> {
+        use std::mem;
+        match self.0.write() {
+            Ok(mut current) => mem::replace(&mut *current, drain),
+            Err(poisoned) => mem::replace(&mut *poisoned.into_inner(), drain),
+        }
+    }
+
+    /// Get the current drain
+    pub fn get(&self) -> ErasedDrain<O, E> {
+        self.drain().current()
+    }
+
+    /// Get an `AtomicSwitch` controlled by this handle
+    pub fn drain(&self) -> AtomicSwitch<O, E> {
+        AtomicSwitch(self.0.clone())
+    }
+}
+
+impl<O, E> Clone for AtomicSwitchCtrl<O, E> {
+    fn clone(&self) -> Self {
+        AtomicSwitchCtrl(self.0.clone())
+    }
+}
@@ -0,0 +1,73 @@
+//! Convert a log written by `slog::binary::Binary` to text or JSON
+//!
+//! ```text
+//! slog-decode [--json] [FILE]
+//! ```
+//!
+//! Reads standard input if no file is given, and writes one line per
+//! record to standard output. Incomplete records (eg. after a crash) are
+//! reported, but are not an error.
+
+extern crate slog;
+
+use slog::binary::Decoder;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::process;
+
+fn usage<W: Write>(mut out: W) {
+    let _ = writeln!(out, "usage: slog-decode [--json] [FILE]");
+}
+
+fn decode<R: Read>(reader: R, json: bool) -> Result<(), String> {
+    let mut decoder = Decoder::new(reader).map_err(|e| e.to_string())?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    for record in &mut decoder {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = if json {
+            record.to_json()
+        } else {
+            record.to_text()
+        };
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    if decoder.truncated() {
+        let _ =
+            writeln!(io::stderr(), "slog-decode: incomplete records skipped");
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut json = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-h" | "--help" => {
+                usage(io::stdout());
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => {
+                usage(io::stderr());
+                process::exit(2);
+            }
+        }
+    }
+
+    let stdin = io::stdin();
+    let result = match path {
+        Some(path) => File::open(&path)
+            .map_err(|e| format!("{}: {}", path, e))
+            .and_then(|file| decode(BufReader::new(file), json)),
+        None => decode(stdin.lock(), json),
+    };
+    if let Err(e) = result {
+        let _ = writeln!(io::stderr(), "slog-decode: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,1016 @@
+//! Compact binary output, and its decoder
+//!
+//! `Binary` is a `Drain` writing records in a binary format, which is much
+//! cheaper to produce than text: values are written with their type, and
+//! numbers as they are, without formatting. File names, module paths, tags
+//! and keys are written once, to a string table, and referred to by their
+//! index after that. `Decoder` reads the records
+//! back, and renders them as text or JSON; the `slog-decode` binary does the
+//! same for a whole file:
+//!
+//! ```text
+//! slog-decode [--json] [FILE]
+//! ```
+//!
+//! ```
+//! #[macro_use]
+//! extern crate slog;
+//!
+//! use slog::binary::{Binary, Decoder};
+//! use slog::*;
+//! use std::fs::File;
+//!
+//! fn main() {
+//!     let path = std::env::temp_dir().join("slog-binary-doc.bin");
+//!     let drain = Binary::new(File::create(&path).unwrap()).unwrap();
+//!     let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+//!     info!(log, "started"; "port" => 8080);
+//!     drop(log);
+//!
+//!     for record in Decoder::new(File::open(&path).unwrap()).unwrap() {
+//!         println!("{}", record.unwrap().to_text());
+//!     }
+//! }
+//! ```
+//!
+//! # Format
+//!
+//! A file starts with `MAGIC` and a `VERSION` byte, followed by entries.
+//! Every entry is its length (4 bytes, little-endian), CRC-32 of the entry
+//! (4 bytes, little-endian), and that many bytes of the entry: its kind
+//! (1 byte) and data. Integers in the data are LEB128 varints if noted, and
+//! little-endian otherwise.
+//!
+//! * String (kind 0): UTF-8 bytes of the next string of the string table,
+//!   always written before the first entry referring to it
+//! * Record (kind 1): seconds (8 bytes) and nanoseconds (4 bytes) since Unix
+//!   epoch, level (1 byte, `Level::as_usize`), and string indices (varints)
+//!   of file, line (varint, not a string index), module and tag; then the
+//!   message: 0 and its string index (varint), or 1 and the message as
+//!   string (as `Binary` writes it); then all the key-value pairs, until the end of the entry: the
+//!   string index of the key (varint), value type (1 byte) and the value
+//!
+//! Strings in records are their length (varint) and UTF-8 bytes. Value
+//! types are, in order of `Serializer` methods: `usize` (0), `isize`, `bool`,
+//! `char`, `u8`, `i8`, `u16`, `i16`, `u32`, `i32`, `f32`, `u64`, `i64`, `f64`
+//! (13), `str`, unit, `None`, arguments (17) and serde (18). `usize` and
+//! `isize` are written as 8 bytes, `bool` as 1 byte, `char` as 4 bytes,
+//! floats as IEEE 754 bits, and `str`, arguments (formatted) and serde
+//! (rendered as JSON) as strings. Unit and `None` have no value.
+//!
+//! Every record is written with one `write_all` call, together with the
+//! strings it introduces, so a crash can leave at most the last entry
+//! incomplete. `Decoder` stops before it, and reports it with
+//! `Decoder::truncated`. Entries of unknown kinds are skipped.
+//!
+//! A file can hold several sessions: a `Binary` appending to an existing
+//! file writes a new header, and starts a new string table. `Decoder` reads
+//! all the sessions, skipping an incomplete entry of a session cut by a
+//! crash before the next one started. Only entries with a wrong checksum,
+//! or cut by the end of input, are searched for a header, so strings
+//! containing `MAGIC` are never taken for one.
+
+use core::fmt::{self, Write as FmtWrite};
+use core::{mem, result};
+use std::collections::HashMap;
+use std::error;
+use std::io::{self, Read};
+use std::string::{String, ToString};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use deflate::crc32;
+use enrich::Rfc3339;
+use json::{self, write_str};
+use key::key_str;
+use logfmt;
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {Drain, Error, Key, Level, OwnedKVList, OwnedValue, Record, Result, KV};
+
+/// Magic bytes at the start of a file
+pub const MAGIC: [u8; 7] = *b"SLOGBIN";
+/// Version of the format, written after `MAGIC`
+pub const VERSION: u8 = 1;
+
+/// Kind of an entry with a string of the string table
+const STRING: u8 = 0;
+/// Kind of an entry with a record
+const RECORD: u8 = 1;
+
+/// Message as index of a string, read but not written
+const MSG_INTERNED: u8 = 0;
+/// Message as string
+const MSG_INLINE: u8 = 1;
+
+/// Seconds since Unix epoch of the last second of year 9999, the latest time
+/// RFC 3339 can represent
+const MAX_SECS: u64 = 253_402_300_799;
+
+const USIZE: u8 = 0;
+const ISIZE: u8 = 1;
+const BOOL: u8 = 2;
+const CHAR: u8 = 3;
+const U8: u8 = 4;
+const I8: u8 = 5;
+const U16: u8 = 6;
+const I16: u8 = 7;
+const U32: u8 = 8;
+const I32: u8 = 9;
+const F32: u8 = 10;
+const U64: u8 = 11;
+const I64: u8 = 12;
+const F64: u8 = 13;
+const STR: u8 = 14;
+const UNIT: u8 = 15;
+const NONE: u8 = 16;
+const ARGUMENTS: u8 = 17;
+const SERDE: u8 = 18;
+
+fn put_varint(out: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x80 {
+        out.push(val as u8 | 0x80);
+        val >>= 7;
+    }
+    out.push(val as u8);
+}
+
+/// Write the lowest `len` bytes of `val`, little-endian
+fn put_le(out: &mut Vec<u8>, val: u64, len: usize) {
+    for i in 0..len {
+        out.push((val >> (8 * i)) as u8);
+    }
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Write length and checksum of `entry`, and `entry`
+fn put_entry(out: &mut Vec<u8>, entry: &[u8]) {
+    put_le(out, entry.len() as u64, 4);
+    put_le(out, u64::from(crc32(entry)), 4);
+    out.extend_from_slice(entry);
+}
+
+/// String table of `Encoder`
+#[derive(Debug, Default)]
+struct Strings {
+    ids: HashMap<String, u32>,
+    /// Entries of strings added while encoding the current record
+    entries: Vec<u8>,
+}
+
+impl Strings {
+    /// Index of `s`, added to the table if needed
+    fn id(&mut self, s: &str) -> u64 {
+        if let Some(&id) = self.ids.get(s) {
+            return u64::from(id);
+        }
+        let id = self.ids.len() as u32;
+        self.ids.insert(s.to_string(), id);
+        let mut entry = Vec::with_capacity(1 + s.len());
+        entry.push(STRING);
+        entry.extend_from_slice(s.as_bytes());
+        put_entry(&mut self.entries, &entry);
+        u64::from(id)
+    }
+
+    /// Remove strings with index `from` and above, which were not written
+    fn forget(&mut self, from: u32) {
+        let ids = mem::replace(&mut self.ids, HashMap::new());
+        self.ids = ids.into_iter().filter(|&(_, id)| id < from).collect();
+    }
+}
+
+/// `Serializer` writing key-value pairs of a record encoded by `Encoder`
+///
+/// Keys are added to the string table, and values are written with their
+/// type; see the module documentation for details.
+pub struct Serializer<'a> {
+    strings: &'a mut Strings,
+    out: &'a mut Vec<u8>,
+    /// Buffer for values which have to be formatted
+    scratch: &'a mut String,
+}
+
+impl<'a> Serializer<'a> {
+    fn key(&mut self, key: &Key, kind: u8) {
+        let id = self.strings.id(key_str(key));
+        put_varint(self.out, id);
+        self.out.push(kind);
+    }
+}
+
+macro_rules! emit_le(
+    ($f:ident, $t:ty, $kind:expr) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            self.key(&key, $kind);
+            put_le(self.out, val as u64, mem::size_of::<$t>());
+            Ok(())
+        }
+    };
+);
+
+impl<'a> ::Serializer for Serializer<'a> {
+    emit_le!(emit_u8, u8, U8);
+    emit_le!(emit_i8, i8, I8);
+    emit_le!(emit_u16, u16, U16);
+    emit_le!(emit_i16, i16, I16);
+    emit_le!(emit_u32, u32, U32);
+    emit_le!(emit_i32, i32, I32);
+    emit_le!(emit_u64, u64, U64);
+    emit_le!(emit_i64, i64, I64);
+
+    fn emit_f32(&mut self, key: Key, val: f32) -> Result {
+        self.key(&key, F32);
+        // `f32::to_bits` is not available on the oldest supported Rust
+        let bits: u32 = unsafe { mem::transmute(val) };
+        put_le(self.out, u64::from(bits), 4);
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> Result {
+        self.key(&key, F64);
+        let bits: u64 = unsafe { mem::transmute(val) };
+        put_le(self.out, bits, 8);
+        Ok(())
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> Result {
+        self.key(&key, USIZE);
+        put_le(self.out, val as u64, 8);
+        Ok(())
+    }
+
+    fn emit_isize(&mut self, key: Key, val: isize) -> Result {
+        self.key(&key, ISIZE);
+        put_le(self.out, val as u64, 8);
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> Result {
+        self.key(&key, BOOL);
+        self.out.push(val as u8);
+        Ok(())
+    }
+
+    fn emit_char(&mut self, key: Key, val: char) -> Result {
+        self.key(&key, CHAR);
+        put_le(self.out, val as u64, 4);
+        Ok(())
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> Result {
+        self.key(&key, STR);
+        put_str(self.out, val);
+        Ok(())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        self.key(&key, UNIT);
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        self.key(&key, NONE);
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        self.scratch.clear();
+        self.scratch.write_fmt(*val)?;
+        self.key(&key, ARGUMENTS);
+        put_str(self.out, self.scratch);
+        Ok(())
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        self.scratch.clear();
+        json::write_serde(self.scratch, value)?;
+        self.key(&key, SERDE);
+        put_str(self.out, self.scratch);
+        Ok(())
+    }
+}
+
+/// Encoder of records in the binary format
+///
+/// The string table is kept between records, so all the records encoded
+/// have to be written to the same file, in order.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    strings: Strings,
+    /// Record entry being encoded
+    entry: Vec<u8>,
+    scratch: String,
+}
+
+impl Encoder {
+    /// Create `Encoder` with an empty string table
+    pub fn new() -> Self {
+        Encoder::default()
+    }
+
+    /// Append entries of `record` and strings it introduces to `out`
+    ///
+    /// On error, nothing is appended, and the string table is left as it
+    /// was.
+    pub fn encode(
+        &mut self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+        out: &mut Vec<u8>,
+    ) -> Result {
+        let mark = self.strings.ids.len() as u32;
+        self.strings.entries.clear();
+        self.entry.clear();
+        match self.encode_record(record, logger_values) {
+            Ok(()) => {
+                out.extend_from_slice(&self.strings.entries);
+                put_entry(out, &self.entry);
+                Ok(())
+            }
+            Err(e) => {
+                self.strings.forget(mark);
+                Err(e)
+            }
+        }
+    }
+
+    fn encode_record(
+        &mut self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> Result {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::new(0, 0));
+        let entry = &mut self.entry;
+        entry.push(RECORD);
+        put_le(entry, since_epoch.as_secs(), 8);
+        put_le(entry, u64::from(since_epoch.subsec_nanos()), 4);
+        entry.push(record.level().as_usize() as u8);
+        put_varint(entry, self.strings.id(record.file()));
+        put_varint(entry, u64::from(record.line()));
+        put_varint(entry, self.strings.id(record.module()));
+        put_varint(entry, self.strings.id(record.tag()));
+        // messages without arguments can't be told apart on the oldest
+        // supported Rust, so none are interned
+        self.scratch.clear();
+        self.scratch.write_fmt(*record.msg())?;
+        entry.push(MSG_INLINE);
+        put_str(entry, &self.scratch);
+
+        let mut serializer = Serializer {
+            strings: &mut self.strings,
+            out: entry,
+            scratch: &mut self.scratch,
+        };
+        record.kv().serialize(record, &mut serializer)?;
+        logger_values.serialize(record, &mut serializer)
+    }
+}
+
+struct State<W> {
+    writer: W,
+    encoder: Encoder,
+    buf: Vec<u8>,
+}
+
+/// `Drain` writing records in the binary format
+///
+/// The header is written by `Binary::new`, starting a new session: `writer`
+/// can be at the start of a new file, or at the end of an existing one (eg.
+/// opened with `OpenOptions::append`), even if it was cut by a crash; see
+/// the module documentation. Key-value pairs of the record are written
+/// first, and then of its `Logger` (children before parents).
+///
+/// Every record is encoded and written with one `write_all` call while
+/// holding a lock of the writer, and the writer is flushed by
+/// `Drain::flush` only, so wrapping it in `io::BufWriter` is a good idea
+/// for files (at the cost of losing more records on a crash).
+pub struct Binary<W: io::Write> {
+    state: Mutex<State<W>>,
+}
+
+impl<W: io::Write> Binary<W> {
+    /// Create `Binary` writing to `writer`, and write the header
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        Ok(Binary {
+            state: Mutex::new(State {
+                writer: writer,
+                encoder: Encoder::new(),
+                buf: Vec::with_capacity(256),
+            }),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<State<W>> {
+        // the string table is only updated after a successful write
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl<W: io::Write> Drain for Binary<W> {
+    type Ok = ();
+    type Err = Error;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let mut state = self.lock();
+        let State {
+            ref mut writer,
+            ref mut encoder,
+            ref mut buf,
+        } = *state;
+        let mark = encoder.strings.ids.len() as u32;
+        buf.clear();
+        encoder.encode(record, logger_values, buf)?;
+        if let Err(e) = writer.write_all(buf) {
+            encoder.strings.forget(mark);
+            return Err(e.into());
+        }
+        Ok(())
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    fn flush(&self) -> Result {
+        Ok(self.lock().writer.flush()?)
+    }
+    fn shutdown(&self) -> Result {
+        self.flush()
+    }
+}
+
+/// Error returned by `Decoder`
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Input does not start with `MAGIC`
+    NotBinaryLog,
+    /// Input is in a version of the format this `Decoder` does not support
+    UnsupportedVersion(u8),
+    /// Input is not valid in the format
+    Malformed,
+    /// Reading failed
+    Io(io::Error),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::NotBinaryLog => write!(f, "not a binary log"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported binary log version {}", version)
+            }
+            DecodeError::Malformed => write!(f, "malformed binary log"),
+            DecodeError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for DecodeError {
+    fn description(&self) -> &str {
+        match *self {
+            DecodeError::NotBinaryLog => "not a binary log",
+            DecodeError::UnsupportedVersion(_) => {
+                "unsupported binary log version"
+            }
+            DecodeError::Malformed => "malformed binary log",
+            DecodeError::Io(_) => "I/O error",
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+/// Value of a decoded key-value pair
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue {
+    /// Value emitted with any `Serializer` method but `emit_serde`
+    Value(OwnedValue),
+    /// `SerdeValue`, as JSON
+    Json(String),
+}
+
+/// Formats the value like `OwnedValue` does, and JSON as it is
+impl fmt::Display for DecodedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodedValue::Value(ref v) => write!(f, "{}", v),
+            DecodedValue::Json(ref json) => write!(f, "{}", json),
+        }
+    }
+}
+
+impl DecodedValue {
+    fn write_json(&self, out: &mut String) {
+        let v = match *self {
+            DecodedValue::Value(ref v) => v,
+            DecodedValue::Json(ref json) => return out.push_str(json),
+        };
+        match *v {
+            OwnedValue::Bool(v) => {
+                out.push_str(if v { "true" } else { "false" })
+            }
+            OwnedValue::Char(v) => write_str(out, v.encode_utf8(&mut [0; 4])),
+            OwnedValue::F32(v) => json::write_f32(out, v),
+            OwnedValue::F64(v) => json::write_f64(out, v),
+            OwnedValue::Str(ref v) | OwnedValue::Arguments(ref v) => {
+                write_str(out, v)
+            }
+            OwnedValue::Unit | OwnedValue::None => out.push_str("null"),
+            // integers
+            ref v => {
+                let _ = write!(out, "{}", v);
+            }
+        }
+    }
+}
+
+/// Record read by `Decoder`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedRecord {
+    time: SystemTime,
+    level: Level,
+    file: String,
+    line: u32,
+    module: String,
+    tag: String,
+    msg: String,
+    kv: Vec<(String, DecodedValue)>,
+}
+
+impl DecodedRecord {
+    /// Get the time the record was logged at
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Get the level
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Get the file
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Get the line
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Get the module
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// Get the tag
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Get the (formatted) message
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// Get key-value pairs of the record and its `Logger`, in order they
+    /// were written in
+    pub fn kv(&self) -> &[(String, DecodedValue)] {
+        &self.kv
+    }
+
+    /// Render as a line of text (without a newline): timestamp, level and
+    /// message, followed by key-value pairs in logfmt
+    pub fn to_text(&self) -> String {
+        let mut out = String::with_capacity(128);
+        let _ = write!(
+            out,
+            "{} {} {}",
+            Rfc3339(self.time),
+            self.level.as_short_str(),
+            self.msg
+        );
+        let mut value = String::new();
+        for &(ref key, ref val) in &self.kv {
+            out.push(' ');
+            logfmt::write_key(&mut out, key);
+            out.push('=');
+            value.clear();
+            let _ = write!(value, "{}", val);
+            logfmt::write_value(&mut out, &value);
+        }
+        out
+    }
+
+    /// Render as a JSON object (without a newline): `ts`, `level`, `msg`,
+    /// `file`, `line`, `module` and `tag` (if not empty), followed by
+    /// key-value pairs
+    pub fn to_json(&self) -> String {
+        let mut out = String::with_capacity(256);
+        let _ = write!(out, "{{\"ts\":\"{}\",\"level\":", Rfc3339(self.time));
+        write_str(&mut out, self.level.as_str());
+        out.push_str(",\"msg\":");
+        write_str(&mut out, &self.msg);
+        out.push_str(",\"file\":");
+        write_str(&mut out, &self.file);
+        let _ = write!(out, ",\"line\":{},\"module\":", self.line);
+        write_str(&mut out, &self.module);
+        if !self.tag.is_empty() {
+            out.push_str(",\"tag\":");
+            write_str(&mut out, &self.tag);
+        }
+        for &(ref key, ref val) in &self.kv {
+            out.push(',');
+            write_str(&mut out, key);
+            out.push(':');
+            val.write_json(&mut out);
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// Reader of data of an entry
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> result::Result<&'a [u8], DecodeError> {
+        if len > self.data.len() {
+            return Err(DecodeError::Malformed);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    /// Read a `len` bytes long little-endian integer
+    fn le(&mut self, len: usize) -> result::Result<u64, DecodeError> {
+        self.bytes(len).map(le)
+    }
+
+    fn u8(&mut self) -> result::Result<u8, DecodeError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn varint(&mut self) -> result::Result<u64, DecodeError> {
+        let mut val = 0u64;
+        let mut shift = 0;
+        while shift < 64 {
+            let byte = self.u8()?;
+            val |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(val);
+            }
+            shift += 7;
+        }
+        Err(DecodeError::Malformed)
+    }
+
+    fn string(&mut self) -> result::Result<String, DecodeError> {
+        let len = self.varint()?;
+        if len > self.data.len() as u64 {
+            return Err(DecodeError::Malformed);
+        }
+        let bytes = self.bytes(len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Malformed)
+    }
+}
+
+/// Reader of the binary format
+///
+/// Iterates over all the records of all the sessions, and stops after the
+/// first error. An incomplete entry is not an error: it's skipped if a new
+/// session follows it, or iteration just stops before it at the end of the
+/// input, and `Decoder::truncated` returns `true`.
+pub struct Decoder<R: io::Read> {
+    reader: R,
+    // bytes read past a header of a new session, to be read again
+    pending: io::Cursor<Vec<u8>>,
+    strings: Vec<String>,
+    truncated: bool,
+    done: bool,
+}
+
+impl<R: io::Read> Decoder<R> {
+    /// Create `Decoder` reading from `reader`, and read the header
+    pub fn new(mut reader: R) -> result::Result<Self, DecodeError> {
+        let mut header = [0; 8];
+        if read_full(&mut reader, &mut header)? < header.len()
+            || header[..7] != MAGIC
+        {
+            return Err(DecodeError::NotBinaryLog);
+        }
+        if header[7] != VERSION {
+            return Err(DecodeError::UnsupportedVersion(header[7]));
+        }
+        Ok(Decoder {
+            reader: reader,
+            pending: io::Cursor::new(vec![]),
+            strings: vec![],
+            truncated: false,
+            done: false,
+        })
+    }
+
+    /// Returns `true` if an incomplete entry was skipped, or ended the input
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn input(&mut self) -> io::Chain<&mut io::Cursor<Vec<u8>>, &mut R> {
+        (&mut self.pending).chain(&mut self.reader)
+    }
+
+    /// Read the next entry with its length and checksum, or `None` at the
+    /// end of input
+    ///
+    /// Returns the bytes read, and `false` if the input ended before the end
+    /// of the entry. A header of a new session is returned as an incomplete
+    /// entry.
+    fn read_entry(
+        &mut self,
+    ) -> result::Result<Option<(Vec<u8>, bool)>, DecodeError> {
+        let mut raw = vec![0; 8];
+        let read = read_full(&mut self.input(), &mut raw)?;
+        raw.truncate(read);
+        if read == 0 {
+            return Ok(None);
+        } else if read < 8 || raw[..4] == MAGIC[..4] {
+            // cut by the end of input, or a header of a new session after a
+            // complete entry
+            return Ok(Some((raw, false)));
+        }
+        let len = le(&raw[..4]);
+        if len == 0 {
+            return Err(DecodeError::Malformed);
+        }
+        self.input().take(len).read_to_end(&mut raw)?;
+        let complete = raw.len() == 8 + len as usize;
+        Ok(Some((raw, complete)))
+    }
+
+    /// Start a new session with a header at `pos` of `raw` bytes of an entry
+    fn restart(
+        &mut self,
+        raw: &[u8],
+        pos: usize,
+    ) -> result::Result<(), DecodeError> {
+        let version = raw[pos + MAGIC.len()];
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        if pos > 0 {
+            // incomplete entry of the previous session
+            self.truncated = true;
+        }
+        self.unread(&raw[pos + MAGIC.len() + 1..]);
+        self.strings.clear();
+        Ok(())
+    }
+
+    /// Find a header of a new session starting in `raw` bytes of an entry,
+    /// and ending after them
+    ///
+    /// If `raw` ends with a part of a header, the rest of it is read, and
+    /// appended to `raw` if it completes the header.
+    fn split_header(
+        &mut self,
+        raw: &mut Vec<u8>,
+    ) -> result::Result<Option<usize>, DecodeError> {
+        let part = match (1..MAGIC.len() + 1)
+            .rev()
+            .find(|&part| raw.ends_with(&MAGIC[..part]))
+        {
+            Some(part) => part,
+            None => return Ok(None),
+        };
+        let end = raw.len();
+        let mut rest = [0; 8];
+        let rest = &mut rest[..MAGIC.len() + 1 - part];
+        let read = read_full(&mut self.input(), rest)?;
+        raw.extend_from_slice(&rest[..read]);
+        match find_header(&raw[end - part..]) {
+            Some(pos) => Ok(Some(end - part + pos)),
+            None => {
+                self.unread(&raw[end..]);
+                raw.truncate(end);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read `bytes` again, before the rest of the input
+    fn unread(&mut self, bytes: &[u8]) {
+        let mut pending = bytes.to_vec();
+        let unread = self.pending.position() as usize;
+        pending.extend_from_slice(&self.pending.get_ref()[unread..]);
+        self.pending = io::Cursor::new(pending);
+    }
+
+    fn string(&self, id: u64) -> result::Result<String, DecodeError> {
+        self.strings
+            .get(id as usize)
+            .cloned()
+            .ok_or(DecodeError::Malformed)
+    }
+
+    fn decode_record(
+        &self,
+        data: &mut Cursor,
+    ) -> result::Result<DecodedRecord, DecodeError> {
+        let secs = data.le(8)?;
+        let nanos = data.le(4)? as u32;
+        if secs > MAX_SECS || nanos >= 1_000_000_000 {
+            return Err(DecodeError::Malformed);
+        }
+        let time = UNIX_EPOCH + Duration::new(secs, nanos);
+        let level = Level::from_usize(usize::from(data.u8()?))
+            .ok_or(DecodeError::Malformed)?;
+        let file = self.string(data.varint()?)?;
+        let line = data.varint()? as u32;
+        let module = self.string(data.varint()?)?;
+        let tag = self.string(data.varint()?)?;
+        let msg = match data.u8()? {
+            MSG_INTERNED => self.string(data.varint()?)?,
+            MSG_INLINE => data.string()?,
+            _ => return Err(DecodeError::Malformed),
+        };
+        let mut kv = vec![];
+        while !data.is_empty() {
+            let key = self.string(data.varint()?)?;
+            kv.push((key, decode_value(data)?));
+        }
+        Ok(DecodedRecord {
+            time: time,
+            level: level,
+            file: file,
+            line: line,
+            module: module,
+            tag: tag,
+            msg: msg,
+            kv: kv,
+        })
+    }
+
+    fn decode_entry(
+        &mut self,
+        entry: &[u8],
+    ) -> result::Result<Option<DecodedRecord>, DecodeError> {
+        let mut data = Cursor { data: entry };
+        match data.u8()? {
+            STRING => {
+                let s = String::from_utf8(data.data.to_vec())
+                    .map_err(|_| DecodeError::Malformed)?;
+                self.strings.push(s);
+            }
+            RECORD => return self.decode_record(&mut data).map(Some),
+            // entries added in later minor versions of the format
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    fn next_record(
+        &mut self,
+    ) -> result::Result<Option<DecodedRecord>, DecodeError> {
+        while let Some((mut raw, complete)) = self.read_entry()? {
+            if complete && valid_checksum(&raw) {
+                match self.decode_entry(&raw[8..])? {
+                    Some(record) => return Ok(Some(record)),
+                    None => continue,
+                }
+            }
+            // entry cut by a header of a new session, or the end of input
+            let header = match find_header(&raw) {
+                Some(pos) => Some(pos),
+                None => self.split_header(&mut raw)?,
+            };
+            match header {
+                Some(pos) => self.restart(&raw, pos)?,
+                None if complete => return Err(DecodeError::Malformed),
+                None => {
+                    self.truncated = true;
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: io::Read> Iterator for Decoder<R> {
+    type Item = result::Result<DecodedRecord, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.next_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn decode_value(
+    data: &mut Cursor,
+) -> result::Result<DecodedValue, DecodeError> {
+    let value = match data.u8()? {
+        USIZE => OwnedValue::Usize(data.le(8)? as usize),
+        ISIZE => OwnedValue::Isize(data.le(8)? as isize),
+        BOOL => OwnedValue::Bool(data.u8()? != 0),
+        CHAR => OwnedValue::Char(
+            ::core::char::from_u32(data.le(4)? as u32)
+                .ok_or(DecodeError::Malformed)?,
+        ),
+        U8 => OwnedValue::U8(data.u8()?),
+        I8 => OwnedValue::I8(data.u8()? as i8),
+        U16 => OwnedValue::U16(data.le(2)? as u16),
+        I16 => OwnedValue::I16(data.le(2)? as i16),
+        U32 => OwnedValue::U32(data.le(4)? as u32),
+        I32 => OwnedValue::I32(data.le(4)? as i32),
+        F32 => {
+            let bits = data.le(4)? as u32;
+            OwnedValue::F32(unsafe { mem::transmute::<u32, f32>(bits) })
+        }
+        U64 => OwnedValue::U64(data.le(8)?),
+        I64 => OwnedValue::I64(data.le(8)? as i64),
+        F64 => {
+            let bits = data.le(8)?;
+            OwnedValue::F64(unsafe { mem::transmute::<u64, f64>(bits) })
+        }
+        STR => OwnedValue::Str(data.string()?),
+        UNIT => OwnedValue::Unit,
+        NONE => OwnedValue::None,
+        ARGUMENTS => OwnedValue::Arguments(data.string()?),
+        SERDE => return Ok(DecodedValue::Json(data.string()?)),
+        _ => return Err(DecodeError::Malformed),
+    };
+    Ok(DecodedValue::Value(value))
+}
+
+/// Little-endian integer in `bytes`
+fn le(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |val, &byte| val << 8 | u64::from(byte))
+}
+
+/// Returns `true` if the checksum of a complete `raw` entry matches it
+fn valid_checksum(raw: &[u8]) -> bool {
+    le(&raw[4..8]) == u64::from(crc32(&raw[8..]))
+}
+
+/// Find `MAGIC` followed by a version byte in `raw`
+fn find_header(raw: &[u8]) -> Option<usize> {
+    raw.windows(MAGIC.len() + 1)
+        .position(|window| window[..MAGIC.len()] == MAGIC)
+}
+
+/// Read into `buf` until it's full or the end of input, and return the
+/// number of bytes read
+fn read_full<R: io::Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
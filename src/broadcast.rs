@@ -0,0 +1,221 @@
+use core::result;
+use std::boxed::Box;
+use std::panic::UnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::vec::Vec;
+
+use {
+    Drain, Level, Never, OwnedKVList, Record, Result,
+    SendSyncRefUnwindSafeDrain,
+};
+
+/// What `Broadcast` does with errors of one of its branches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Ignore errors
+    Ignore,
+    /// Return the error from `Broadcast::log`
+    ///
+    /// The record is still passed to all the other branches. If multiple
+    /// branches fail, the first error is returned.
+    Propagate,
+    /// Ignore errors, but stop passing records to the branch after given
+    /// number of consecutive failures
+    DisableAfter(usize),
+}
+
+/// Adapter dropping `Ok` values, so drains with any `Ok` can be branches
+struct DropOk<D>(D);
+
+impl<D: Drain> Drain for DropOk<D> {
+    type Ok = ();
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        self.0.log(record, logger_values).map(|_| ())
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.0.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.0.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.0.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.0.shutdown()
+    }
+}
+
+struct Branch<E> {
+    drain: Box<SendSyncRefUnwindSafeDrain<Ok = (), Err = E>>,
+    policy: ErrorPolicy,
+    failures: AtomicUsize,
+    disabled: AtomicBool,
+}
+
+// trait objects can't be `UnwindSafe` on the oldest supported Rust, so
+// `Branch` implements it, as `Broadcast::add` only takes such drains
+impl<E> UnwindSafe for Branch<E> {}
+
+/// `Drain` passing records to any number of drains
+///
+/// Like `Duplicate`, but over a list of type-erased drains that can be built
+/// at runtime (eg. from configuration), each with its own `ErrorPolicy`.
+/// Branches are called in the order they were added, skipping the ones not
+/// enabled at the record's level.
+///
+/// `is_enabled` returns `true` if any of the (not disabled) branches is
+/// enabled at given level.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = Broadcast::new()
+///     .add(Discard, ErrorPolicy::Propagate)
+///     .add(Discard, ErrorPolicy::DisableAfter(3));
+/// # let _ = drain;
+/// ```
+pub struct Broadcast<E = Never> {
+    branches: Vec<Branch<E>>,
+}
+
+impl<E> Broadcast<E> {
+    /// Create `Broadcast` without any branches
+    pub fn new() -> Self {
+        Broadcast { branches: vec![] }
+    }
+
+    /// Add a branch passing records to `drain`
+    ///
+    /// `Ok` values returned by `drain` are discarded.
+    pub fn add<D>(mut self, drain: D, policy: ErrorPolicy) -> Self
+    where
+        D: SendSyncRefUnwindSafeDrain<Err = E> + UnwindSafe + 'static,
+    {
+        self.branches.push(Branch {
+            drain: Box::new(DropOk(drain)),
+            policy: policy,
+            failures: AtomicUsize::new(0),
+            disabled: AtomicBool::new(false),
+        });
+        self
+    }
+
+    /// Number of branches
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Returns `true` if there are no branches
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    /// Returns `true` if the branch at `index` was disabled by
+    /// `ErrorPolicy::DisableAfter`
+    pub fn is_disabled(&self, index: usize) -> bool {
+        match self.branches.get(index) {
+            Some(branch) => branch.disabled.load(Ordering::Relaxed),
+            None => false,
+        }
+    }
+
+    fn for_each_branch<F>(&self, f: F) -> Result
+    where
+        F: Fn(&SendSyncRefUnwindSafeDrain<Ok = (), Err = E>) -> Result,
+    {
+        let mut first_err = Ok(());
+        for branch in &self.branches {
+            if branch.disabled.load(Ordering::Relaxed) {
+                continue;
+            }
+            let res = f(&*branch.drain);
+            if branch.policy == ErrorPolicy::Propagate {
+                first_err = first_err.and(res);
+            }
+        }
+        first_err
+    }
+}
+
+impl<E> Default for Broadcast<E> {
+    fn default() -> Self {
+        Broadcast::new()
+    }
+}
+
+impl<E> Drain for Broadcast<E> {
+    type Ok = ();
+    type Err = E;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let mut first_err = None;
+        for branch in &self.branches {
+            if branch.disabled.load(Ordering::Relaxed)
+                || !branch.drain.is_enabled(record.level())
+            {
+                continue;
+            }
+            match branch.drain.log(record, logger_values) {
+                Ok(()) => branch.failures.store(0, Ordering::Relaxed),
+                Err(e) => match branch.policy {
+                    ErrorPolicy::Ignore => {}
+                    ErrorPolicy::Propagate => {
+                        if first_err.is_none() {
+                            first_err = Some(e);
+                        }
+                    }
+                    ErrorPolicy::DisableAfter(n) => {
+                        let failures =
+                            branch.failures.fetch_add(1, Ordering::Relaxed) + 1;
+                        if failures >= n {
+                            branch.disabled.store(true, Ordering::Relaxed);
+                        }
+                    }
+                },
+            }
+        }
+        match first_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// Flush all the branches
+    ///
+    /// Returns the first error of branches with `ErrorPolicy::Propagate`.
+    fn flush(&self) -> Result {
+        self.for_each_branch(|drain| drain.flush())
+    }
+    /// Shut down all the branches
+    ///
+    /// Returns the first error of branches with `ErrorPolicy::Propagate`.
+    fn shutdown(&self) -> Result {
+        self.for_each_branch(|drain| drain.shutdown())
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.branches.iter().any(|branch| {
+            !branch.disabled.load(Ordering::Relaxed)
+                && branch.drain.is_enabled(level)
+        })
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        // branches are only ever disabled, never enabled again
+        self.branches
+            .iter()
+            .all(|branch| branch.drain.is_interest_static())
+    }
+}
@@ -0,0 +1,256 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use collections::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::boxed::Box;
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+use lazy::Lazy;
+use {Level, RecordStatic};
+
+/// `CallSiteState::index` value of a call site not registered yet
+const UNREGISTERED: usize = 0;
+/// `CallSiteState::index` value of a call site being registered
+const REGISTERING: usize = !0;
+
+/// Address of the head of the list of registered call sites, or 0
+#[allow(deprecated)]
+static CALL_SITES: AtomicUsize = ::core::sync::atomic::ATOMIC_USIZE_INIT;
+/// Number of registered call sites
+#[allow(deprecated)]
+static REGISTERED: AtomicUsize = ::core::sync::atomic::ATOMIC_USIZE_INIT;
+/// Interest epoch; answers cached in earlier epochs are not valid anymore
+#[allow(deprecated)]
+static EPOCH: AtomicUsize = ::core::sync::atomic::ATOMIC_USIZE_INIT;
+
+/// Logging statement (`log!` macro expansion)
+///
+/// Every logging statement has a `static CallSite`, registered the first time
+/// the statement is executed. `Logger` uses it to cache whether its drain is
+/// enabled for the level of the statement (see `Logger::is_interested`).
+///
+/// All the registered call sites can be listed with `call_sites`.
+pub struct CallSite {
+    #[doc(hidden)]
+    pub record_static: &'static RecordStatic<'static>,
+    #[doc(hidden)]
+    pub state: CallSiteState,
+}
+
+/// Registration state of a `CallSite`
+///
+/// Used by logging macros; not considered a part of stable API.
+#[doc(hidden)]
+pub struct CallSiteState {
+    /// `UNREGISTERED`, `REGISTERING`, or index of the call site plus one
+    index: AtomicUsize,
+    /// Address of the next registered call site, or 0
+    next: AtomicUsize,
+}
+
+/// `CallSiteState` of a new `CallSite`
+///
+/// Statics can't call functions on the oldest supported Rust, so logging
+/// macros create `CallSite`s with this constant instead of a constructor.
+#[doc(hidden)]
+#[allow(deprecated)]
+#[cfg_attr(
+    clippy,
+    allow(renamed_and_removed_lints, declare_interior_mutable_const)
+)]
+pub const CALL_SITE_STATE_INIT: CallSiteState = CallSiteState {
+    index: ::core::sync::atomic::ATOMIC_USIZE_INIT,
+    next: ::core::sync::atomic::ATOMIC_USIZE_INIT,
+};
+
+impl CallSite {
+    /// Get level, tag and code location of the logging statement
+    pub fn record_static(&self) -> &'static RecordStatic<'static> {
+        self.record_static
+    }
+
+    /// Get level of the logging statement
+    pub fn level(&self) -> Level {
+        self.record_static.level
+    }
+
+    /// Get index of the call site, registering it if needed
+    ///
+    /// Returns `None` if the call site is being registered by another
+    /// thread at the moment.
+    fn index(&'static self) -> Option<usize> {
+        match self.state.index.load(Ordering::Acquire) {
+            REGISTERING => None,
+            UNREGISTERED => self.register(),
+            index => Some(index - 1),
+        }
+    }
+
+    fn register(&'static self) -> Option<usize> {
+        if self
+            .state
+            .index
+            .compare_exchange(
+                UNREGISTERED,
+                REGISTERING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // registered by another thread in the meantime
+            return match self.state.index.load(Ordering::Acquire) {
+                REGISTERING | UNREGISTERED => None,
+                index => Some(index - 1),
+            };
+        }
+        let index = REGISTERED.fetch_add(1, Ordering::Relaxed);
+        let this = self as *const CallSite as usize;
+        let mut head = CALL_SITES.load(Ordering::Acquire);
+        loop {
+            self.state.next.store(head, Ordering::Relaxed);
+            match CALL_SITES.compare_exchange_weak(
+                head,
+                this,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+        self.state.index.store(index + 1, Ordering::Release);
+        Some(index)
+    }
+}
+
+/// Iterator over registered call sites, returned by `call_sites`
+pub struct CallSites(*const CallSite);
+
+impl Iterator for CallSites {
+    type Item = &'static CallSite;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_null() {
+            return None;
+        }
+        // addresses in the list are only ever taken from
+        // `&'static CallSite`s, in `CallSite::register`
+        let call_site: &'static CallSite = unsafe { &*self.0 };
+        self.0 = call_site.state.next.load(Ordering::Acquire) as *const _;
+        Some(call_site)
+    }
+}
+
+/// List logging statements executed so far, most recently registered first
+///
+/// A logging statement registers its `CallSite` when it's executed for the
+/// first time (after passing the static level check, see
+/// `__slog_static_max_level`), so statements that never ran are not listed.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// fn main() {
+///     let log = slog::Logger::root(slog::Discard, o!());
+///     info!(log, "starting");
+///     for call_site in slog::call_sites() {
+///         let rs = call_site.record_static();
+///         println!("{}:{} {}", rs.location.file, rs.location.line, rs.level);
+///     }
+/// }
+/// ```
+pub fn call_sites() -> CallSites {
+    CallSites(CALL_SITES.load(Ordering::Acquire) as *const _)
+}
+
+/// Invalidate interest cached for all the call sites
+///
+/// `Logger`s cache results of `Drain::is_enabled` per call site for drains
+/// declaring their interest static (`Drain::is_interest_static`). Such a
+/// `Drain`, if it can start returning `true` for a level it returned `false`
+/// for before (eg. after its filtering was reconfigured), must call this
+/// function after the change, or records of call sites considered disabled
+/// would still be skipped. `DynamicLevel` does it already.
+pub fn invalidate_interest() {
+    EPOCH.fetch_add(1, Ordering::AcqRel);
+}
+
+/// Number of cached answers in one chunk of `InterestCache`
+const CHUNK_LEN: usize = 128;
+/// Number of chunks of `InterestCache`; answers for call sites with higher
+/// indices are not cached
+const CHUNKS: usize = 128;
+
+/// Cached answer is valid
+const VALID: usize = 0b01;
+/// Cached answer is "enabled"
+const ENABLED: usize = 0b10;
+
+/// Answers of `Drain::is_enabled` for call sites, cached by a `Logger` (and
+/// shared by all its children)
+///
+/// Every entry holds an answer with `EPOCH` it was asked in; answers of past
+/// epochs are asked again. Chunks of entries are allocated on first use.
+pub struct InterestCache {
+    chunks: Box<[Lazy<Box<[AtomicUsize]>>]>,
+}
+
+impl InterestCache {
+    pub fn new() -> Self {
+        InterestCache {
+            chunks: (0..CHUNKS)
+                .map(|_| lazy!())
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+        }
+    }
+
+    fn chunk(&self, i: usize) -> &[AtomicUsize] {
+        self.chunks[i].get(|| {
+            (0..CHUNK_LEN)
+                .map(|_| AtomicUsize::new(0))
+                .collect::<Vec<_>>()
+                .into_boxed_slice()
+        })
+    }
+
+    /// Get cached answer for `call_site`, or ask `is_enabled` and cache it
+    pub fn is_enabled<F>(
+        &self,
+        call_site: &'static CallSite,
+        is_enabled: F,
+    ) -> bool
+    where
+        F: FnOnce(Level) -> bool,
+    {
+        // epoch has to be loaded before asking, so a change made while
+        // asking is never cached as a valid answer
+        let epoch = EPOCH.load(Ordering::Acquire);
+        let index = match call_site.index() {
+            Some(index) if index < CHUNK_LEN * CHUNKS => index,
+            _ => return is_enabled(call_site.level()),
+        };
+        let entry = &self.chunk(index / CHUNK_LEN)[index % CHUNK_LEN];
+        let tag = epoch.wrapping_shl(2) | VALID;
+        let cached = entry.load(Ordering::Relaxed);
+        if cached & !ENABLED == tag {
+            return cached & ENABLED != 0;
+        }
+        let enabled = is_enabled(call_site.level());
+        entry.store(tag | if enabled { ENABLED } else { 0 }, Ordering::Relaxed);
+        enabled
+    }
+}
+
+impl Drop for InterestCache {
+    fn drop(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.take();
+        }
+    }
+}
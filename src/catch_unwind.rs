@@ -0,0 +1,166 @@
+use core::{cmp, fmt, result};
+use std::any::Any;
+use std::boxed::Box;
+use std::panic::{self, AssertUnwindSafe};
+use std::string::{String, ToString};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use {Drain, Error, Level, OwnedKVList, Record, Result};
+
+/// Error returned by `CatchUnwind`
+#[derive(Debug)]
+pub enum CatchUnwindError<E> {
+    /// Error returned by the wrapped drain
+    Drain(E),
+    /// The wrapped drain panicked, with given message (if the panic payload
+    /// was a string)
+    Panic(Option<String>),
+}
+
+impl<E: fmt::Display> fmt::Display for CatchUnwindError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CatchUnwindError::Drain(ref e) => write!(f, "{}", e),
+            CatchUnwindError::Panic(Some(ref msg)) => {
+                write!(f, "drain panicked: {}", msg)
+            }
+            CatchUnwindError::Panic(None) => write!(f, "drain panicked"),
+        }
+    }
+}
+
+impl<E> ::std::error::Error for CatchUnwindError<E>
+where
+    E: fmt::Debug + fmt::Display,
+{
+    fn description(&self) -> &str {
+        match *self {
+            CatchUnwindError::Drain(_) => "drain failed",
+            CatchUnwindError::Panic(_) => "drain panicked",
+        }
+    }
+}
+
+fn panic_message(payload: &Box<Any + Send>) -> Option<String> {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        Some(msg.to_string())
+    } else {
+        payload.downcast_ref::<String>().cloned()
+    }
+}
+
+/// `Drain` isolating panics of another drain
+///
+/// Runs the wrapped drain under `std::panic::catch_unwind`, so a panic in
+/// it, or in a `Value` serialized by it, is returned as
+/// `CatchUnwindError::Panic` instead of unwinding through the logging
+/// thread. Panics in `Drain::flush` and `Drain::shutdown` are returned as
+/// `Error::Other`.
+///
+/// A drain that panicked may be left in an inconsistent state. With
+/// `CatchUnwind::disable_after`, the wrapped drain is not used anymore after
+/// given number of consecutive panics: records are then dropped, and
+/// `Drain::is_enabled` returns `false`.
+///
+/// Note that the panic hook still runs for every panic, so by default the
+/// panic message is still printed to `stderr`.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = CatchUnwind::new(Discard).disable_after(3).ignore_res();
+/// # let _ = drain;
+/// ```
+pub struct CatchUnwind<D: Drain> {
+    drain: D,
+    disable_after: Option<usize>,
+    panics: AtomicUsize,
+    disabled: AtomicBool,
+}
+
+impl<D: Drain> CatchUnwind<D> {
+    /// Create `CatchUnwind` wrapping `drain`, never disabling it
+    pub fn new(drain: D) -> Self {
+        CatchUnwind {
+            drain: drain,
+            disable_after: None,
+            panics: AtomicUsize::new(0),
+            disabled: AtomicBool::new(false),
+        }
+    }
+
+    /// Stop using the wrapped drain after `panics` consecutive panics
+    pub fn disable_after(mut self, panics: usize) -> Self {
+        self.disable_after = Some(cmp::max(panics, 1));
+        self
+    }
+
+    /// Returns `true` if the wrapped drain was disabled after panicking
+    pub fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    fn catch<T, F>(&self, f: F) -> result::Result<T, Box<Any + Send>>
+    where
+        F: FnOnce() -> T,
+    {
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(res) => {
+                self.panics.store(0, Ordering::Relaxed);
+                Ok(res)
+            }
+            Err(payload) => {
+                let panics = self.panics.fetch_add(1, Ordering::Relaxed) + 1;
+                if let Some(limit) = self.disable_after {
+                    if panics >= limit {
+                        self.disabled.store(true, Ordering::Relaxed);
+                    }
+                }
+                Err(payload)
+            }
+        }
+    }
+}
+
+impl<D: Drain> Drain for CatchUnwind<D> {
+    type Ok = Option<D::Ok>;
+    type Err = CatchUnwindError<D::Err>;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        if self.is_disabled() {
+            return Ok(None);
+        }
+        match self.catch(|| self.drain.log(record, logger_values)) {
+            Ok(Ok(ok)) => Ok(Some(ok)),
+            Ok(Err(e)) => Err(CatchUnwindError::Drain(e)),
+            Err(payload) => {
+                Err(CatchUnwindError::Panic(panic_message(&payload)))
+            }
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        !self.is_disabled() && self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    fn flush(&self) -> Result {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        self.catch(|| self.drain.flush())
+            .unwrap_or(Err(Error::Other))
+    }
+    fn shutdown(&self) -> Result {
+        if self.is_disabled() {
+            return Ok(());
+        }
+        self.catch(|| self.drain.shutdown())
+            .unwrap_or(Err(Error::Other))
+    }
+}
@@ -0,0 +1,257 @@
+use core::result;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use owned::{serialize_kv, OwnedRecord, OwnedValue};
+use {Drain, Key, Level, OwnedKVList, Record, Result, SingleKV};
+
+/// Last passed record, and its suppressed repetitions
+struct Last {
+    record: OwnedRecord,
+    logger_kv: Vec<(Key, OwnedValue)>,
+    repeated: usize,
+    since: Instant,
+}
+
+impl Last {
+    fn new(record: OwnedRecord, logger_kv: Vec<(Key, OwnedValue)>) -> Self {
+        Last {
+            record: record,
+            logger_kv: logger_kv,
+            repeated: 0,
+            since: Instant::now(),
+        }
+    }
+
+    fn matches(
+        &self,
+        record: &OwnedRecord,
+        logger_kv: &[(Key, OwnedValue)],
+    ) -> bool {
+        let (a, b) = (self.record.location(), record.location());
+        self.record.level() == record.level()
+            && (a.file, a.line, a.column) == (b.file, b.line, b.column)
+            && self.record.tag() == record.tag()
+            && self.record.msg() == record.msg()
+            && self.record.kv() == record.kv()
+            && self.logger_kv[..] == *logger_kv
+    }
+
+    /// Pass the record with a count of suppressed repetitions, if any
+    ///
+    /// The count is kept if `drain` fails.
+    fn flush<D: Drain>(
+        &mut self,
+        drain: &D,
+    ) -> result::Result<Option<D::Ok>, D::Err> {
+        if self.repeated == 0 {
+            return Ok(None);
+        }
+        let repeated = SingleKV::from(("repeated", self.repeated));
+        let values = self.record.logger_values();
+        let ok = self
+            .record
+            .as_record_with(&repeated, |record| drain.log(record, values))?;
+        self.repeated = 0;
+        self.since = Instant::now();
+        Ok(Some(ok))
+    }
+}
+
+/// `Drain` collapsing consecutive duplicate records
+///
+/// Works like "last message repeated N times" of syslogd: a `Record` with the
+/// same level, code location, tag, message and key-value pairs (both of the
+/// record and its `Logger`) as the previous one is not passed to the wrapped
+/// drain, only counted. When a different record arrives, the repeated one is
+/// passed again, with an additional `repeated` key carrying the number of
+/// suppressed copies, just before the new one.
+///
+/// Pending repetitions are also flushed when a duplicate arrives more than
+/// `flush_timeout` after the record was last passed, or after `max_repeats`
+/// copies were suppressed, so a never-ending stream of duplicates is still
+/// reported periodically. Anything left pending is flushed on `Drain::flush`,
+/// and when `Dedup` is dropped.
+///
+/// `Dedup` has no timer of its own, so when the duplicates just stop, their
+/// count is only reported with the next record. To report it once
+/// `flush_timeout` passes, use `Dedup::flush_periodically`, or call
+/// `Dedup::pass_expired` from a timer of your own.
+///
+/// Records that fail to serialize are passed through as they are.
+///
+/// ```
+/// use slog::*;
+/// use std::time::Duration;
+///
+/// let drain = Dedup::new(Discard)
+///     .flush_timeout(Duration::from_secs(30))
+///     .max_repeats(10_000);
+/// # let _ = drain;
+/// ```
+pub struct Dedup<D: Drain> {
+    drain: D,
+    flush_timeout: Duration,
+    max_repeats: usize,
+    last: Mutex<Option<Last>>,
+}
+
+impl<D: Drain> Dedup<D> {
+    /// Create `Dedup` wrapping given `drain`
+    ///
+    /// By default repetitions are flushed at least every 30 seconds, with no
+    /// limit on their number.
+    pub fn new(drain: D) -> Self {
+        Dedup {
+            drain: drain,
+            flush_timeout: Duration::from_secs(30),
+            max_repeats: ::core::usize::MAX,
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Set how long repetitions can stay pending
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Set how many repetitions can be collapsed into one record
+    pub fn max_repeats(mut self, max_repeats: usize) -> Self {
+        self.max_repeats = max_repeats;
+        self
+    }
+
+    /// Pass pending repetitions, if the record was last passed more than
+    /// `flush_timeout` ago
+    ///
+    /// Wrap `Dedup` in an `Arc` to be able to call it after passing the
+    /// drain to a `Logger`.
+    pub fn pass_expired(&self) -> result::Result<Option<D::Ok>, D::Err> {
+        let mut last = match self.last.lock() {
+            Ok(last) => last,
+            Err(_) => return Ok(None),
+        };
+        match *last {
+            Some(ref mut last)
+                if last.since.elapsed() >= self.flush_timeout =>
+            {
+                last.flush(&self.drain)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Spawn a thread calling `Dedup::pass_expired` every `interval`
+    ///
+    /// The thread holds only a weak reference to `dedup`, and exits after
+    /// it is dropped. Errors of the wrapped drain are ignored.
+    ///
+    /// ```
+    /// use slog::*;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let drain = Dedup::new(Discard).flush_timeout(Duration::from_secs(10));
+    /// let drain = Arc::new(drain);
+    /// Dedup::flush_periodically(&drain, Duration::from_secs(1));
+    /// let _log = Logger::root(drain.fuse(), o!());
+    /// ```
+    pub fn flush_periodically(
+        dedup: &Arc<Self>,
+        interval: Duration,
+    ) -> thread::JoinHandle<()>
+    where
+        D: Send + Sync + 'static,
+    {
+        let dedup = Arc::downgrade(dedup);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match dedup.upgrade() {
+                Some(dedup) => {
+                    let _ = dedup.pass_expired();
+                }
+                None => return,
+            }
+        })
+    }
+
+    fn pass_repeated(&self) {
+        if let Ok(mut last) = self.last.lock() {
+            if let Some(ref mut last) = *last {
+                let _ = last.flush(&self.drain);
+            }
+        }
+    }
+}
+
+impl<D: Drain> Drain for Dedup<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let owned = OwnedRecord::new(record, logger_values).and_then(|owned| {
+            serialize_kv(record, logger_values).map(|kv| (owned, kv))
+        });
+        let (owned, logger_kv) = match owned {
+            Ok(owned) => owned,
+            Err(_) => return self.drain.log(record, logger_values).map(Some),
+        };
+
+        // Lock is held while logging, so repetitions are always reported
+        // before the record that ended them.
+        let mut last = match self.last.lock() {
+            Ok(last) => last,
+            Err(_) => return self.drain.log(record, logger_values).map(Some),
+        };
+
+        let mut summary = Ok(None);
+        if let Some(ref mut last) = *last {
+            if last.matches(&owned, &logger_kv) {
+                last.repeated += 1;
+                if last.repeated >= self.max_repeats
+                    || last.since.elapsed() >= self.flush_timeout
+                {
+                    last.flush(&self.drain)?;
+                }
+                return Ok(None);
+            }
+            summary = last.flush(&self.drain);
+        }
+
+        // The new record is passed even if the summary failed, and the
+        // failure reported after it.
+        *last = Some(Last::new(owned, logger_kv));
+        let ok = self.drain.log(record, logger_values)?;
+        summary.map(|_| Some(ok))
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    /// Pass pending repetitions, and flush the wrapped drain
+    fn flush(&self) -> Result {
+        self.pass_repeated();
+        self.drain.flush()
+    }
+    /// Pass pending repetitions, and shut down the wrapped drain
+    fn shutdown(&self) -> Result {
+        self.pass_repeated();
+        self.drain.shutdown()
+    }
+}
+
+impl<D: Drain> Drop for Dedup<D> {
+    fn drop(&mut self) {
+        self.pass_repeated();
+    }
+}
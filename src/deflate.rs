@@ -0,0 +1,260 @@
+//! Minimal DEFLATE (RFC 1951) compressor, with zlib (RFC 1950) and gzip
+//! (RFC 1952) wrappers
+//!
+//! Compresses with LZ77 matching and the fixed Huffman codes only, which is
+//! much simpler than a full compressor, and good enough for repetitive
+//! data like log messages.
+
+use core::cmp;
+use std::vec::Vec;
+
+/// Writer of bits, least significant first
+struct Bits {
+    out: Vec<u8>,
+    acc: u32,
+    len: u32,
+}
+
+impl Bits {
+    fn write(&mut self, bits: u32, len: u32) {
+        self.acc |= bits << self.len;
+        self.len += len;
+        while self.len >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    /// Write Huffman `code`, which is stored most significant bit first
+    fn write_code(&mut self, code: u32, len: u32) {
+        let mut reversed = 0;
+        for i in 0..len {
+            reversed |= ((code >> i) & 1) << (len - 1 - i);
+        }
+        self.write(reversed, len);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Base lengths of length codes 257..=285
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59,
+    67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+/// Extra bits of length codes 257..=285
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5,
+    5, 5, 5, 0,
+];
+/// Base distances of distance codes 0..=29
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513,
+    769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+/// Extra bits of distance codes 0..=29
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10,
+    11, 11, 12, 12, 13, 13,
+];
+
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW: usize = 32 * 1024;
+const HASH_BITS: u32 = 12;
+/// Number of earlier positions tried for every match
+const MAX_CHAIN: usize = 32;
+
+/// Write literal/length `symbol` with the fixed Huffman code
+fn write_symbol(bits: &mut Bits, symbol: u32) {
+    match symbol {
+        _ if symbol < 144 => bits.write_code(0x30 + symbol, 8),
+        _ if symbol < 256 => bits.write_code(0x190 + symbol - 144, 9),
+        _ if symbol < 280 => bits.write_code(symbol - 256, 7),
+        _ => bits.write_code(0xc0 + symbol - 280, 8),
+    }
+}
+
+/// Index of the last entry in `base` not greater than `value`
+fn code_index(base: &[u16], value: usize) -> usize {
+    base.iter().rposition(|&b| b as usize <= value).unwrap_or(0)
+}
+
+fn write_match(bits: &mut Bits, len: usize, dist: usize) {
+    let i = code_index(&LENGTH_BASE, len);
+    write_symbol(bits, 257 + i as u32);
+    bits.write(
+        (len - LENGTH_BASE[i] as usize) as u32,
+        u32::from(LENGTH_EXTRA[i]),
+    );
+    let i = code_index(&DIST_BASE, dist);
+    bits.write_code(i as u32, 5);
+    bits.write(
+        (dist - DIST_BASE[i] as usize) as u32,
+        u32::from(DIST_EXTRA[i]),
+    );
+}
+
+fn hash(data: &[u8]) -> usize {
+    let h = (u32::from(data[0]) << 16)
+        | (u32::from(data[1]) << 8)
+        | u32::from(data[2]);
+    (h.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// Chains of earlier positions with the same hash of next 3 bytes
+struct Matcher<'a> {
+    data: &'a [u8],
+    /// Last position with given hash
+    head: Vec<usize>,
+    /// Previous position with the same hash, for every position
+    prev: Vec<usize>,
+}
+
+const NONE: usize = ::core::usize::MAX;
+
+impl<'a> Matcher<'a> {
+    fn insert(&mut self, pos: usize) {
+        if pos + MIN_MATCH <= self.data.len() {
+            let h = hash(&self.data[pos..]);
+            self.prev[pos] = self.head[h];
+            self.head[h] = pos;
+        }
+    }
+
+    /// Find longest match of data at `pos` (before inserting `pos`), as
+    /// length and distance
+    fn find(&self, pos: usize) -> (usize, usize) {
+        let (mut best_len, mut best_dist) = (0, 0);
+        if pos + MIN_MATCH > self.data.len() {
+            return (best_len, best_dist);
+        }
+        let max_len = cmp::min(MAX_MATCH, self.data.len() - pos);
+        let mut candidate = self.head[hash(&self.data[pos..])];
+        let mut tries = 0;
+        while candidate != NONE
+            && pos - candidate <= WINDOW
+            && tries < MAX_CHAIN
+        {
+            let len = self.data[candidate..]
+                .iter()
+                .zip(&self.data[pos..pos + max_len])
+                .take_while(|&(a, b)| a == b)
+                .count();
+            if len > best_len {
+                best_len = len;
+                best_dist = pos - candidate;
+                if len == max_len {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+            tries += 1;
+        }
+        (best_len, best_dist)
+    }
+}
+
+/// Compress `data` as raw DEFLATE stream (one block with fixed codes)
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bits = Bits {
+        out: Vec::with_capacity(data.len() / 2 + 16),
+        acc: 0,
+        len: 0,
+    };
+    // BFINAL, and BTYPE 01 (fixed Huffman codes)
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut matcher = Matcher {
+        data: data,
+        head: vec![NONE; 1 << HASH_BITS],
+        prev: vec![NONE; data.len()],
+    };
+    let mut pos = 0;
+    while pos < data.len() {
+        let (len, dist) = matcher.find(pos);
+        if len >= MIN_MATCH {
+            write_match(&mut bits, len, dist);
+            for p in pos..pos + len {
+                matcher.insert(p);
+            }
+            pos += len;
+        } else {
+            write_symbol(&mut bits, u32::from(data[pos]));
+            matcher.insert(pos);
+            pos += 1;
+        }
+    }
+    write_symbol(&mut bits, 256);
+    bits.finish()
+}
+
+/// Adler-32 checksum of `data`
+pub fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65_521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // sums can't overflow in 5552 bytes
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// CRC-32 (IEEE 802.3) checksum of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Bytes of `val`, least significant first
+fn le_bytes(val: u32) -> [u8; 4] {
+    [
+        val as u8,
+        (val >> 8) as u8,
+        (val >> 16) as u8,
+        (val >> 24) as u8,
+    ]
+}
+
+/// Compress `data` in zlib format
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate with 32K window, "fastest" level, and a valid header check
+    let mut out = vec![0x78, 0x01];
+    out.extend_from_slice(&deflate(data));
+    let mut sum = le_bytes(adler32(data));
+    sum.reverse();
+    out.extend_from_slice(&sum);
+    out
+}
+
+/// Compress `data` in gzip format
+pub fn gzip(data: &[u8]) -> Vec<u8> {
+    // deflate, no flags, no modification time, no extra flags, unknown OS
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&le_bytes(crc32(data)));
+    out.extend_from_slice(&le_bytes(data.len() as u32));
+    out
+}
@@ -0,0 +1,247 @@
+use core::str::FromStr;
+use core::{cmp, fmt, result};
+use std::env;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use {Drain, FilterLevel, Level, OwnedKVList, Record, Result};
+
+/// Logging level directives, per module
+///
+/// Parsed from `env_logger`-like strings: a comma separated list of
+/// `module::path=level` pairs, and optionally one bare `level` used for
+/// modules not matching any of the paths, eg.
+/// `"info,hyper=warn,myapp::db=trace"`. Levels are parsed like
+/// `FilterLevel` (`off` disables logging completely). A bare
+/// `module::path` which is not a level enables everything from the module,
+/// like `module::path=trace`.
+///
+/// A path matches a module if it's equal to the module path, or is one of
+/// its parent modules (`myapp::db` matches `myapp::db::pool`, but not
+/// `myapp::dbx`). When multiple paths match, the longest one wins. Without a
+/// bare `level`, modules not matching any path are not logged at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directives {
+    default: FilterLevel,
+    modules: Vec<(String, FilterLevel)>,
+}
+
+impl Directives {
+    /// Create `Directives` logging every module at `default` level
+    pub fn new(default: FilterLevel) -> Self {
+        Directives {
+            default: default,
+            modules: vec![],
+        }
+    }
+
+    /// Add a directive for `module` and all its submodules
+    ///
+    /// Replaces previous directive for the same path.
+    pub fn module(mut self, module: &str, level: FilterLevel) -> Self {
+        self.modules.retain(|&(ref name, _)| name != module);
+        self.modules.push((module.to_string(), level));
+        // longest paths first, so the first match is the best one
+        self.modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        self
+    }
+
+    /// Parse directives from environment variable `name`
+    ///
+    /// If the variable is not set, only `Error` and `Critical` records are
+    /// logged.
+    pub fn from_env(name: &str) -> result::Result<Self, DirectivesError> {
+        match env::var(name) {
+            Ok(s) => s.parse(),
+            Err(env::VarError::NotPresent) => {
+                Ok(Directives::new(FilterLevel::Error))
+            }
+            Err(env::VarError::NotUnicode(_)) => {
+                Err(DirectivesError::NotUnicode)
+            }
+        }
+    }
+
+    /// Level enabled for records from `module`
+    pub fn level_for(&self, module: &str) -> FilterLevel {
+        for &(ref name, level) in &self.modules {
+            if module.starts_with(name.as_str())
+                && (module.len() == name.len()
+                    || module[name.len()..].starts_with("::"))
+            {
+                return level;
+            }
+        }
+        self.default
+    }
+
+    /// Most verbose level enabled for any module
+    fn max_level(&self) -> FilterLevel {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, cmp::max)
+    }
+}
+
+fn parse_level(level: &str) -> result::Result<FilterLevel, DirectivesError> {
+    if level.is_empty() {
+        return Err(DirectivesError::Level(level.to_string()));
+    }
+    level
+        .parse()
+        .map_err(|_| DirectivesError::Level(level.to_string()))
+}
+
+impl FromStr for Directives {
+    type Err = DirectivesError;
+    fn from_str(s: &str) -> result::Result<Directives, DirectivesError> {
+        let mut directives = Directives::new(FilterLevel::Off);
+        for directive in s.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            let mut parts = directive.splitn(2, '=');
+            let first = parts.next().unwrap_or("").trim();
+            match parts.next().map(str::trim) {
+                Some(_) if first.is_empty() => {
+                    return Err(DirectivesError::Directive(
+                        directive.to_string(),
+                    ))
+                }
+                Some(level) => {
+                    directives = directives.module(first, parse_level(level)?);
+                }
+                None => match first.parse() {
+                    Ok(level) => directives.default = level,
+                    Err(_) => {
+                        directives =
+                            directives.module(first, FilterLevel::Trace)
+                    }
+                },
+            }
+        }
+        Ok(directives)
+    }
+}
+
+/// Error returned when parsing `Directives` fails
+#[derive(Debug, Clone, PartialEq)]
+pub enum DirectivesError {
+    /// Directive is not `level`, `module` or `module=level`
+    Directive(String),
+    /// Unknown logging level
+    Level(String),
+    /// Environment variable is not valid unicode
+    NotUnicode,
+}
+
+impl fmt::Display for DirectivesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DirectivesError::Directive(ref d) => {
+                write!(f, "invalid logging directive: `{}`", d)
+            }
+            DirectivesError::Level(ref l) => {
+                write!(f, "invalid logging level: `{}`", l)
+            }
+            DirectivesError::NotUnicode => {
+                write!(f, "logging directives are not valid unicode")
+            }
+        }
+    }
+}
+
+impl ::std::error::Error for DirectivesError {
+    fn description(&self) -> &str {
+        match *self {
+            DirectivesError::Directive(_) => "invalid logging directive",
+            DirectivesError::Level(_) => "invalid logging level",
+            DirectivesError::NotUnicode => {
+                "logging directives are not valid unicode"
+            }
+        }
+    }
+}
+
+/// `Drain` filtering records by module path, using `Directives`
+///
+/// Replaces hand-written `Filter`s over `Record::module()`. Also implements
+/// `Drain::is_enabled`: a level is enabled if any of the directives enables
+/// it, so logging statements at levels disabled for all modules are skipped
+/// early.
+///
+/// ```
+/// use slog::*;
+///
+/// let directives = "info,hyper=warn,myapp::db=trace".parse().unwrap();
+/// let drain = DirectiveFilter::new(Discard, directives);
+/// # let _ = drain;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DirectiveFilter<D: Drain> {
+    drain: D,
+    directives: Directives,
+    max_level: FilterLevel,
+}
+
+impl<D: Drain> DirectiveFilter<D> {
+    /// Create `DirectiveFilter` wrapping given `drain`
+    pub fn new(drain: D, directives: Directives) -> Self {
+        DirectiveFilter {
+            drain: drain,
+            max_level: directives.max_level(),
+            directives: directives,
+        }
+    }
+
+    /// Create `DirectiveFilter` with directives from environment variable
+    /// `name`
+    ///
+    /// See `Directives::from_env`.
+    pub fn from_env(
+        drain: D,
+        name: &str,
+    ) -> result::Result<Self, DirectivesError> {
+        Directives::from_env(name).map(|d| DirectiveFilter::new(drain, d))
+    }
+
+    /// Get directives used by this filter
+    pub fn directives(&self) -> &Directives {
+        &self.directives
+    }
+}
+
+impl<D: Drain> Drain for DirectiveFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let level = self.directives.level_for(record.module());
+        if record.level().as_usize() <= level.as_usize() {
+            Ok(Some(self.drain.log(record, logger_values)?))
+        } else {
+            Ok(None)
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        level.as_usize() <= self.max_level.as_usize()
+            && self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,112 @@
+use core::result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use {invalidate_interest, Drain, Level, OwnedKVList, Record, Result};
+
+/// Handle changing the level of a `DynamicLevelFilter`
+///
+/// It can be cloned and sent between threads freely. All clones control the
+/// same level.
+#[derive(Debug, Clone)]
+pub struct DynamicLevel(Arc<AtomicUsize>);
+
+impl DynamicLevel {
+    /// Create `DynamicLevel` starting at given `level`
+    pub fn new(level: Level) -> Self {
+        DynamicLevel(Arc::new(AtomicUsize::new(level.as_usize())))
+    }
+
+    /// Get current level
+    pub fn get(&self) -> Level {
+        Level::from_usize(self.0.load(Ordering::Relaxed))
+            .unwrap_or(Level::Trace)
+    }
+
+    /// Set current level
+    ///
+    /// Takes effect for all following logging statements, in all threads.
+    pub fn set(&self, level: Level) {
+        self.0.store(level.as_usize(), Ordering::Relaxed);
+        invalidate_interest();
+    }
+}
+
+/// `Drain` passing records at least as important as a level that can be
+/// changed at runtime
+///
+/// Works like `LevelFilter`, but the level is kept in an atomic, shared with
+/// all `DynamicLevel` handles obtained with `DynamicLevelFilter::handle`, so
+/// eg. debug logging can be turned on in a running process. `is_enabled`
+/// reads the current level too, so code guarded by `Logger::is_enabled`
+/// follows the changes.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = DynamicLevelFilter::new(Discard, Level::Info);
+/// let level = drain.handle();
+///
+/// // eg. on `SIGUSR1`:
+/// level.set(Level::Debug);
+/// # let _ = drain;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DynamicLevelFilter<D: Drain> {
+    drain: D,
+    level: DynamicLevel,
+}
+
+impl<D: Drain> DynamicLevelFilter<D> {
+    /// Create `DynamicLevelFilter` starting at given `level`
+    pub fn new(drain: D, level: Level) -> Self {
+        DynamicLevelFilter::with_handle(drain, DynamicLevel::new(level))
+    }
+
+    /// Create `DynamicLevelFilter` controlled by an existing handle
+    ///
+    /// Useful for changing the level of multiple drains at once.
+    pub fn with_handle(drain: D, level: DynamicLevel) -> Self {
+        DynamicLevelFilter {
+            drain: drain,
+            level: level,
+        }
+    }
+
+    /// Get a handle changing the level of this filter
+    pub fn handle(&self) -> DynamicLevel {
+        self.level.clone()
+    }
+}
+
+impl<D: Drain> Drain for DynamicLevelFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        if record.level().is_at_least(self.level.get()) {
+            Ok(Some(self.drain.log(record, logger_values)?))
+        } else {
+            Ok(None)
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        level.is_at_least(self.level.get()) && self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,381 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{fmt, result};
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::panic::RefUnwindSafe;
+use std::string::{String, ToString};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use key::key_from_static;
+use lazy::Lazy;
+use {
+    BorrowedKV, Drain, Level, OwnedKVList, Record, RecordStatic, Result,
+    Serializer, KV,
+};
+
+/// Source of key-value pairs computed for every record, used by `Enrich`
+///
+/// Implemented for closures taking `&Record` and `&mut Serializer`.
+pub trait Provider: Send + Sync + RefUnwindSafe {
+    /// Emit key-value pairs for `record` into `serializer`
+    fn provide(&self, record: &Record, serializer: &mut Serializer) -> Result;
+}
+
+impl<F> Provider for F
+where
+    F: Fn(&Record, &mut Serializer) -> Result + Send + Sync + RefUnwindSafe,
+{
+    fn provide(&self, record: &Record, serializer: &mut Serializer) -> Result {
+        (*self)(record, serializer)
+    }
+}
+
+/// `Display` of a point in time as RFC 3339 UTC timestamp, with
+/// microseconds, eg. `2018-03-28T12:34:56.789012Z`
+///
+/// Times before the Unix epoch are shown as the epoch.
+pub struct Rfc3339(pub SystemTime);
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let since_epoch = self
+            .0
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::new(0, 0));
+        let secs = since_epoch.as_secs();
+        let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+        // civil date from days since the epoch, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719_468;
+        let era = z / 146_097;
+        let doe = z - era * 146_097;
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            since_epoch.subsec_nanos() / 1000
+        )
+    }
+}
+
+/// Wall-clock time of logging, as RFC 3339 UTC timestamp
+pub struct Timestamp(&'static str);
+
+impl Timestamp {
+    /// Create `Timestamp` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        Timestamp(key)
+    }
+}
+
+impl Provider for Timestamp {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        serializer.emit_arguments(
+            key_from_static(self.0),
+            &format_args!("{}", Rfc3339(SystemTime::now())),
+        )
+    }
+}
+
+/// Monotonic time since the first `Uptime` was created, in seconds (as `f64`)
+///
+/// All `Uptime` providers count from the same instant, taken when the first
+/// one is created; create one early (eg. with the root `Logger`) for it to
+/// be close to the process start.
+pub struct Uptime {
+    key: &'static str,
+    start: Instant,
+}
+
+impl Uptime {
+    /// Create `Uptime` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        Uptime {
+            key: key,
+            start: process_start(),
+        }
+    }
+}
+
+/// Instant the first `Uptime` was created
+fn process_start() -> Instant {
+    static START: Lazy<Instant> = lazy!();
+
+    *START.get(Instant::now)
+}
+
+impl Provider for Uptime {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        let uptime = self.start.elapsed();
+        serializer.emit_f64(
+            key_from_static(self.key),
+            uptime.as_secs() as f64 + f64::from(uptime.subsec_nanos()) * 1e-9,
+        )
+    }
+}
+
+/// Name of the logging thread (none for unnamed threads)
+pub struct ThreadName(&'static str);
+
+impl ThreadName {
+    /// Create `ThreadName` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        ThreadName(key)
+    }
+}
+
+impl Provider for ThreadName {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        match thread::current().name() {
+            Some(name) => serializer.emit_str(key_from_static(self.0), name),
+            None => serializer.emit_none(key_from_static(self.0)),
+        }
+    }
+}
+
+/// Id of the logging thread (as `usize`), unique within the process
+///
+/// Threads are numbered from 1, in order they first log through any
+/// `ThreadId`.
+pub struct ThreadId(&'static str);
+
+impl ThreadId {
+    /// Create `ThreadId` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        ThreadId(key)
+    }
+}
+
+/// Number of threads numbered by `ThreadId`
+#[allow(deprecated)]
+static THREAD_IDS: AtomicUsize = ::core::sync::atomic::ATOMIC_USIZE_INIT;
+
+thread_local! {
+    static THREAD_ID: usize = THREAD_IDS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+impl Provider for ThreadId {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        let id = THREAD_ID.with(|id| *id);
+        serializer.emit_usize(key_from_static(self.0), id)
+    }
+}
+
+/// Id of the process
+pub struct ProcessId(&'static str);
+
+impl ProcessId {
+    /// Create `ProcessId` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        ProcessId(key)
+    }
+}
+
+impl Provider for ProcessId {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        serializer.emit_u32(key_from_static(self.0), process_id())
+    }
+}
+
+/// Id of the current process
+///
+/// `std::process::id` is not available on the oldest supported Rust.
+#[cfg(unix)]
+pub fn process_id() -> u32 {
+    extern "C" {
+        fn getpid() -> i32;
+    }
+    // `getpid` can't fail
+    unsafe { getpid() as u32 }
+}
+
+/// Id of the current process
+///
+/// `std::process::id` is not available on the oldest supported Rust.
+#[cfg(windows)]
+pub fn process_id() -> u32 {
+    extern "system" {
+        fn GetCurrentProcessId() -> u32;
+    }
+    // `GetCurrentProcessId` can't fail
+    unsafe { GetCurrentProcessId() }
+}
+
+/// Id of the current process (0 on platforms without processes)
+#[cfg(not(any(unix, windows)))]
+pub fn process_id() -> u32 {
+    0
+}
+
+/// Name of the host (none if it can't be found)
+///
+/// Looked up once, on creation: from `HOSTNAME` or `COMPUTERNAME`
+/// environment variable, or `/proc/sys/kernel/hostname` or `/etc/hostname`
+/// file.
+pub struct Hostname {
+    key: &'static str,
+    hostname: Option<String>,
+}
+
+impl Hostname {
+    /// Create `Hostname` emitting under `key`
+    pub fn new(key: &'static str) -> Self {
+        Hostname::with_hostname(key, find_hostname())
+    }
+
+    /// Create `Hostname` emitting given `hostname` under `key`
+    pub fn with_hostname(key: &'static str, hostname: Option<String>) -> Self {
+        Hostname {
+            key: key,
+            hostname: hostname,
+        }
+    }
+}
+
+pub fn find_hostname() -> Option<String> {
+    let vars = ["HOSTNAME", "COMPUTERNAME"];
+    let paths = ["/proc/sys/kernel/hostname", "/etc/hostname"];
+    let from_env = vars.iter().filter_map(|var| env::var(var).ok());
+    let from_files = paths.iter().filter_map(|path| {
+        let mut hostname = String::new();
+        fs::File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut hostname))
+            .ok()
+            .map(|_| hostname)
+    });
+    from_env
+        .chain(from_files)
+        .map(|hostname| hostname.trim().to_string())
+        .find(|hostname| !hostname.is_empty())
+}
+
+impl Provider for Hostname {
+    fn provide(&self, _: &Record, serializer: &mut Serializer) -> Result {
+        match self.hostname {
+            Some(ref hostname) => {
+                serializer.emit_str(key_from_static(self.key), hostname)
+            }
+            None => serializer.emit_none(key_from_static(self.key)),
+        }
+    }
+}
+
+/// `KV` of a record followed by output of providers
+struct EnrichedKV<'a> {
+    kv: BorrowedKV<'a>,
+    providers: &'a [Arc<Provider>],
+}
+
+impl<'a> KV for EnrichedKV<'a> {
+    fn serialize(
+        &self,
+        record: &Record,
+        serializer: &mut Serializer,
+    ) -> Result {
+        self.kv.serialize(record, serializer)?;
+        for provider in self.providers {
+            provider.provide(record, serializer)?;
+        }
+        Ok(())
+    }
+}
+
+/// `Drain` adding key-value pairs computed by `Provider`s to every record
+///
+/// Providers run at `log` time, so unlike `FnValue`s in `Logger` values
+/// they can see the `Record`. Their key-value pairs are passed to the
+/// wrapped drain as record key-value pairs, after the ones of the record
+/// itself.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = Enrich::new(Discard)
+///     .provider(Timestamp::new("ts"))
+///     .provider(Uptime::new("uptime"))
+///     .provider(ThreadName::new("thread"))
+///     .provider(ThreadId::new("thread_id"))
+///     .provider(ProcessId::new("pid"))
+///     .provider(Hostname::new("host"))
+///     .provider(|record: &Record, serializer: &mut Serializer| {
+///         serializer.emit_u32("line".into(), record.line())
+///     });
+/// # let _ = drain;
+/// ```
+pub struct Enrich<D: Drain> {
+    drain: D,
+    providers: Vec<Arc<Provider>>,
+}
+
+impl<D: Drain> Enrich<D> {
+    /// Create `Enrich` without any providers
+    pub fn new(drain: D) -> Self {
+        Enrich {
+            drain: drain,
+            providers: vec![],
+        }
+    }
+
+    /// Add a provider
+    pub fn provider<P: Provider + 'static>(mut self, provider: P) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+}
+
+impl<D: Drain> Drain for Enrich<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let rs = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+        let kv = EnrichedKV {
+            kv: record.kv(),
+            providers: &self.providers,
+        };
+        self.drain.log(
+            &Record::new(&rs, record.msg(), BorrowedKV(&kv)),
+            logger_values,
+        )
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,111 @@
+use core::result;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use owned::OwnedRecord;
+use {Drain, Level, OwnedKVList, Record, Result};
+
+/// `Drain` keeping recent records in memory, and passing them on error
+///
+/// Records at or above `threshold` level are passed to the wrapped drain
+/// right away. Records below it are stored in a ring buffer holding up to
+/// `capacity` of the most recent ones (as `OwnedRecord`s). When a record at
+/// or above `trigger` level (`Error` by default) arrives, the whole buffered
+/// history is passed to the wrapped drain, oldest first, just before the
+/// triggering record, and the buffer is cleared.
+///
+/// This gives detailed context around failures, without paying for
+/// formatting and writing verbose output in steady state.
+///
+/// Records that fail to serialize are not buffered.
+///
+/// ```
+/// use slog::*;
+///
+/// // Pass `Info` and above; keep last 1000 `Debug` and `Trace` records and
+/// // pass them on `Error` or `Critical`.
+/// let drain = FlightRecorder::new(Discard, 1000, Level::Info);
+/// # let _ = drain;
+/// ```
+pub struct FlightRecorder<D: Drain> {
+    drain: D,
+    capacity: usize,
+    threshold: Level,
+    trigger: Level,
+    buffer: Mutex<VecDeque<OwnedRecord>>,
+}
+
+impl<D: Drain> FlightRecorder<D> {
+    /// Create `FlightRecorder` wrapping `drain`, buffering up to `capacity`
+    /// records below `threshold` level
+    pub fn new(drain: D, capacity: usize, threshold: Level) -> Self {
+        FlightRecorder {
+            drain: drain,
+            capacity: capacity,
+            threshold: threshold,
+            trigger: Level::Error,
+            buffer: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Set level of records triggering passing buffered records
+    pub fn trigger_level(mut self, level: Level) -> Self {
+        self.trigger = level;
+        self
+    }
+}
+
+impl<D: Drain> Drain for FlightRecorder<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let level = record.level();
+        if level.is_at_least(self.trigger) {
+            let mut buffer = match self.buffer.lock() {
+                Ok(buffer) => buffer,
+                Err(_) => {
+                    return self.drain.log(record, logger_values).map(Some)
+                }
+            };
+            while let Some(buffered) = buffer.pop_front() {
+                buffered.log_to(&self.drain)?;
+            }
+            self.drain.log(record, logger_values).map(Some)
+        } else if level.is_at_least(self.threshold) {
+            self.drain.log(record, logger_values).map(Some)
+        } else {
+            if self.capacity == 0 {
+                return Ok(None);
+            }
+            if let Ok(owned) = OwnedRecord::new(record, logger_values) {
+                if let Ok(mut buffer) = self.buffer.lock() {
+                    if buffer.len() == self.capacity {
+                        buffer.pop_front();
+                    }
+                    buffer.push_back(owned);
+                }
+            }
+            Ok(None)
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
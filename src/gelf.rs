@@ -0,0 +1,338 @@
+//! GELF (Graylog Extended Log Format) 1.1 output
+//!
+//! `Encoder` renders records as GELF JSON payloads, and `Gelf` is a `Drain`
+//! sending them over UDP, optionally compressed, and split into GELF chunks
+//! when they don't fit in one datagram.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate slog;
+//!
+//! use slog::gelf::{Compression, Gelf};
+//! use slog::*;
+//!
+//! fn main() {
+//!     # let listener = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+//!     # let addr = listener.local_addr().unwrap();
+//!     let drain = Gelf::udp(addr).unwrap().compression(Compression::Gzip);
+//!     let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+//!     info!(log, "started"; "port" => 8080);
+//! }
+//! ```
+
+use core::fmt::{self, Write as FmtWrite};
+use core::hash::{BuildHasher, Hasher};
+use core::{cmp, result};
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::string::{String, ToString};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use deflate;
+use enrich::{find_hostname, process_id};
+use json::{self, write_str, Escaped};
+use syslog::{connect_udp, severity};
+use {Drain, Error, OwnedKVList, Record, Result, KV};
+
+/// Error returned by `Encoder` and `Gelf`
+#[derive(Debug)]
+pub enum GelfError {
+    /// Key-value pair with key `id`, which would be the `_id` field,
+    /// reserved by GELF
+    ReservedKey,
+    /// Message (of given size, after compression) does not fit in 128
+    /// chunks
+    TooLarge(usize),
+    /// Serializing key-value pairs failed
+    Serialize(Error),
+    /// Sending failed
+    Io(io::Error),
+}
+
+impl fmt::Display for GelfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GelfError::ReservedKey => {
+                write!(f, "key `id` is reserved in GELF (as `_id`)")
+            }
+            GelfError::TooLarge(size) => {
+                write!(f, "GELF message of {} bytes is too large", size)
+            }
+            GelfError::Serialize(ref e) => write!(f, "{}", e),
+            GelfError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl ::std::error::Error for GelfError {
+    fn description(&self) -> &str {
+        match *self {
+            GelfError::ReservedKey => "reserved GELF key",
+            GelfError::TooLarge(_) => "GELF message too large",
+            GelfError::Serialize(_) => "serialization error",
+            GelfError::Io(_) => "I/O error",
+        }
+    }
+}
+
+impl From<Error> for GelfError {
+    fn from(e: Error) -> Self {
+        GelfError::Serialize(e)
+    }
+}
+
+impl From<io::Error> for GelfError {
+    fn from(e: io::Error) -> Self {
+        GelfError::Io(e)
+    }
+}
+
+impl From<fmt::Error> for GelfError {
+    fn from(e: fmt::Error) -> Self {
+        GelfError::Serialize(e.into())
+    }
+}
+
+/// Write `key` as name of an additional field: with `_` prefix, and
+/// characters other than letters, digits, `_`, `.` and `-` replaced with
+/// `_`
+fn write_key(out: &mut String, key: &str) {
+    out.push_str("\"_");
+    for c in key.chars() {
+        if (c.is_alphanumeric() && (c as u32) < 0x80)
+            || c == '_'
+            || c == '.'
+            || c == '-'
+        {
+            out.push(c);
+        } else {
+            out.push('_');
+        }
+    }
+    out.push('"');
+}
+
+/// Encoder of records as GELF 1.1 JSON payloads
+///
+/// The message of a record is `short_message`, its level is mapped to
+/// syslog severity `level` (see `syslog::severity`), and its location is
+/// in `_file`, `_line` and `_module` additional fields. Key-value pairs of
+/// the record and its `Logger` are additional fields too, with `_` prefix,
+/// and with characters GELF does not allow in field names replaced with
+/// `_`. Key `id` is rejected (`_id` field is reserved), and of key-value
+/// pairs with the same field name, only the first one emitted is kept (so
+/// values of the record win over values of the `Logger`, and values of
+/// children over their parents).
+#[derive(Debug, Clone)]
+pub struct Encoder {
+    host: String,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Encoder::new()
+    }
+}
+
+impl Encoder {
+    /// Create `Encoder` with the host found like by `Hostname` provider
+    pub fn new() -> Self {
+        Encoder {
+            host: find_hostname().unwrap_or_else(|| "localhost".to_string()),
+        }
+    }
+
+    /// Set `host` field
+    pub fn host(mut self, host: &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    /// Encode `record` as GELF JSON
+    pub fn encode(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<String, GelfError> {
+        let mut kv = json::Serializer::with_key_writer(write_key);
+        record.kv().serialize(record, &mut kv)?;
+        logger_values.serialize(record, &mut kv)?;
+
+        let mut out = String::with_capacity(256);
+        out.push_str("{\"version\":\"1.1\",\"host\":");
+        write_str(&mut out, &self.host);
+        out.push_str(",\"short_message\":\"");
+        Escaped(&mut out).write_fmt(*record.msg())?;
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::new(0, 0));
+        write!(
+            out,
+            "\",\"timestamp\":{}.{:06},\"level\":{},\"_file\":",
+            since_epoch.as_secs(),
+            since_epoch.subsec_nanos() / 1000,
+            severity(record.level())
+        )?;
+        write_str(&mut out, record.file());
+        write!(out, ",\"_line\":{},\"_module\":", record.line())?;
+        write_str(&mut out, record.module());
+
+        let mut seen: HashSet<&str> = ["\"_file\"", "\"_line\"", "\"_module\""]
+            .iter()
+            .cloned()
+            .collect();
+        for i in 0..kv.len() {
+            let key = kv.key(i);
+            if key == "\"_id\"" {
+                return Err(GelfError::ReservedKey);
+            }
+            if seen.insert(key) {
+                out.push(',');
+                kv.write_member(&mut out, i);
+            }
+        }
+        out.push('}');
+        Ok(out)
+    }
+}
+
+/// Compression of GELF messages sent by `Gelf`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Send plain JSON
+    None,
+    /// Compress with zlib
+    Zlib,
+    /// Compress with gzip
+    Gzip,
+}
+
+/// Magic bytes of a chunk
+const CHUNK_MAGIC: [u8; 2] = [0x1e, 0x0f];
+/// Size of a chunk header: magic bytes, message id, sequence number and
+/// sequence count
+const CHUNK_HEADER: usize = 12;
+/// Maximal number of chunks of a message
+const MAX_CHUNKS: usize = 128;
+
+/// `Drain` sending records to Graylog, in GELF over UDP
+///
+/// Records are encoded with `Encoder`, and compressed with given
+/// `Compression` (none by default). Messages larger than the chunk size
+/// (1420 bytes by default, see `Gelf::chunk_size`) are split into chunks,
+/// up to 128 of them; larger messages are dropped with
+/// `GelfError::TooLarge`.
+pub struct Gelf {
+    socket: UdpSocket,
+    encoder: Encoder,
+    compression: Compression,
+    chunk_size: usize,
+    /// Random start of message ids of this drain
+    id_seed: u64,
+    sent: AtomicUsize,
+}
+
+impl Gelf {
+    /// Create `Gelf` sending to `server` over UDP
+    pub fn udp<A: ToSocketAddrs>(server: A) -> io::Result<Self> {
+        let socket = connect_udp(server)?;
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(process_id());
+        if let Ok(since_epoch) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u64(since_epoch.as_secs());
+            hasher.write_u32(since_epoch.subsec_nanos());
+        }
+        Ok(Gelf {
+            socket: socket,
+            encoder: Encoder::new(),
+            compression: Compression::None,
+            chunk_size: 1420,
+            id_seed: hasher.finish(),
+            sent: AtomicUsize::new(0),
+        })
+    }
+
+    /// Set `host` field
+    pub fn host(mut self, host: &str) -> Self {
+        self.encoder = self.encoder.host(host);
+        self
+    }
+
+    /// Set compression
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set maximal size of datagrams, including chunk headers
+    ///
+    /// 1420 (the default) is safe on most networks, 8192 is common for
+    /// local networks.
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = cmp::max(chunk_size, CHUNK_HEADER + 1);
+        self
+    }
+
+    /// Split `payload` into datagrams
+    fn datagrams(
+        &self,
+        payload: Vec<u8>,
+    ) -> result::Result<Vec<Vec<u8>>, GelfError> {
+        if payload.len() <= self.chunk_size {
+            return Ok(vec![payload]);
+        }
+        let data_size = self.chunk_size - CHUNK_HEADER;
+        let count = (payload.len() + data_size - 1) / data_size;
+        if count > MAX_CHUNKS {
+            return Err(GelfError::TooLarge(payload.len()));
+        }
+        let id = self
+            .id_seed
+            .wrapping_add(self.sent.fetch_add(1, Ordering::Relaxed) as u64);
+        let id: Vec<u8> = (0..8).rev().map(|i| (id >> (8 * i)) as u8).collect();
+        Ok(payload
+            .chunks(data_size)
+            .enumerate()
+            .map(|(seq, data)| {
+                let mut chunk = Vec::with_capacity(CHUNK_HEADER + data.len());
+                chunk.extend_from_slice(&CHUNK_MAGIC);
+                chunk.extend_from_slice(&id);
+                chunk.push(seq as u8);
+                chunk.push(count as u8);
+                chunk.extend_from_slice(data);
+                chunk
+            })
+            .collect())
+    }
+}
+
+impl Drain for Gelf {
+    type Ok = ();
+    type Err = GelfError;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let json = self.encoder.encode(record, logger_values)?;
+        let payload = match self.compression {
+            Compression::None => json.into_bytes(),
+            Compression::Zlib => deflate::zlib(json.as_bytes()),
+            Compression::Gzip => deflate::gzip(json.as_bytes()),
+        };
+        for datagram in self.datagrams(payload)? {
+            self.socket.send(&datagram)?;
+        }
+        Ok(())
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    fn flush(&self) -> Result {
+        Ok(())
+    }
+}
@@ -0,0 +1,918 @@
+//! JSON output, without any dependencies
+//!
+//! `Json` is a `Drain` writing every record as one line with a flat JSON
+//! object: timestamp, level and message, followed by key-value pairs of the
+//! record and its `Logger`. `Serializer` can be used on its own to render
+//! key-value pairs as a JSON object.
+//!
+//! ```
+//! #[macro_use]
+//! extern crate slog;
+//!
+//! use slog::json::{DuplicateKeys, Json, KeyOrder};
+//! use slog::*;
+//!
+//! fn main() {
+//!     let drain = Json::new(std::io::stderr())
+//!         .msg_key("message")
+//!         .level_key("severity")
+//!         .key_order(KeyOrder::Sorted)
+//!         .duplicate_keys(DuplicateKeys::First);
+//!     let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+//!     info!(log, "started"; "port" => 8080);
+//! }
+//! ```
+//!
+//! Integers and floats are written as JSON numbers, except for NaN and
+//! infinities, which JSON has no numbers for: they are written as strings
+//! `"NaN"`, `"Infinity"` and `"-Infinity"`. `()` and `None` are written as
+//! `null`. With `nested-values` feature, `SerdeValue`s are written as nested
+//! JSON values.
+
+use core::fmt::{self, Write as FmtWrite};
+use core::result;
+use std::collections::HashSet;
+use std::io;
+use std::string::String;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+use std::vec::Vec;
+
+use enrich::Rfc3339;
+use key::key_str;
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {Drain, Error, Key, OwnedKVList, Record, Result, KV};
+
+/// Order of key-value pairs in the JSON object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// In order they are serialized in: key-value pairs of the record, and
+    /// then of the `Logger` (children before parents)
+    Emitted,
+    /// Key-value pairs of the `Logger` before the ones of the record
+    LoggerFirst,
+    /// Sorted by key (keeping order of duplicate keys)
+    Sorted,
+}
+
+/// Handling of key-value pairs with the same key
+///
+/// JSON objects with duplicate keys are valid, but many consumers keep only
+/// one of the values, so it's better to choose which one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Write all of them
+    Keep,
+    /// Write only the first one emitted, so values of the record win over
+    /// values of the `Logger`, and values of children over their parents
+    First,
+    /// Write only the last one emitted
+    Last,
+}
+
+/// Write `s` as a JSON string, with quotes
+#[doc(hidden)]
+pub fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    escape(out, s);
+    out.push('"');
+}
+
+/// Write `s` escaped for a JSON string, without quotes
+fn escape(out: &mut String, s: &str) {
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escaped = match b {
+            b'"' => "\\\"",
+            b'\\' => "\\\\",
+            b'\n' => "\\n",
+            b'\r' => "\\r",
+            b'\t' => "\\t",
+            0x08 => "\\b",
+            0x0c => "\\f",
+            _ if b < 0x20 => "",
+            _ => continue,
+        };
+        out.push_str(&s[start..i]);
+        if escaped.is_empty() {
+            out.push_str("\\u00");
+            out.push(HEX[usize::from(b >> 4)] as char);
+            out.push(HEX[usize::from(b & 0xf)] as char);
+        } else {
+            out.push_str(escaped);
+        }
+        start = i + 1;
+    }
+    out.push_str(&s[start..]);
+}
+
+const HEX: [u8; 16] = *b"0123456789abcdef";
+
+/// `fmt::Write` escaping everything written for a JSON string
+#[doc(hidden)]
+pub struct Escaped<'a>(pub &'a mut String);
+
+impl<'a> fmt::Write for Escaped<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        escape(self.0, s);
+        Ok(())
+    }
+}
+
+/// Write a float as JSON number, or NaN and infinities as strings
+macro_rules! write_float(
+    ($out:expr, $val:expr) => {{
+        let (out, val) = ($out, $val);
+        if val.is_nan() {
+            out.push_str("\"NaN\"");
+            Ok(())
+        } else if val.is_infinite() {
+            out.push_str(if val > 0.0 { "\"Infinity\"" } else { "\"-Infinity\"" });
+            Ok(())
+        } else {
+            // `Debug` keeps the fraction (`1.0`), and uses exponents for
+            // very big or small numbers, both valid in JSON
+            write!(out, "{:?}", val)
+        }
+    }};
+);
+
+/// Write `val` as JSON, like `Serializer` does
+#[doc(hidden)]
+pub fn write_f32(out: &mut String, val: f32) {
+    let _ = write_float!(out, val);
+}
+
+/// Write `val` as JSON, like `Serializer` does
+#[doc(hidden)]
+pub fn write_f64(out: &mut String, val: f64) {
+    let _ = write_float!(out, val);
+}
+
+/// `Serializer` rendering key-value pairs as members of a JSON object
+///
+/// Members are kept in order they were emitted in, until written out with
+/// `Serializer::finish`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::*;
+///
+/// fn main() {
+///     let mut serializer = json::Serializer::new();
+///     serializer.emit_str("user".into(), "ana \"the admin\"").unwrap();
+///     serializer.emit_f64("load".into(), 0.5).unwrap();
+///     serializer.emit_none("group".into()).unwrap();
+///     assert_eq!(
+///         serializer.finish(),
+///         r#"{"user":"ana \"the admin\"","load":0.5,"group":null}"#
+///     );
+/// }
+/// ```
+pub struct Serializer {
+    /// Keys (as JSON strings) and values (as JSON) of all the members
+    buf: String,
+    /// Start of the key and of the value of every member in `buf`; a member
+    /// ends where the next one starts
+    members: Vec<(usize, usize)>,
+    /// Writes keys as JSON strings
+    write_key: fn(&mut String, &str),
+}
+
+// `fn` with a reference argument is not `Debug` on the oldest supported Rust
+impl fmt::Debug for Serializer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Serializer")
+            .field("buf", &self.buf)
+            .field("members", &self.members)
+            .finish()
+    }
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Serializer::with_key_writer(write_str)
+    }
+}
+
+impl Serializer {
+    /// Create `Serializer` without any members
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    /// Create `Serializer` writing keys with `write_key`, which has to
+    /// write a JSON string
+    #[doc(hidden)]
+    pub fn with_key_writer(write_key: fn(&mut String, &str)) -> Self {
+        Serializer {
+            buf: String::new(),
+            members: vec![],
+            write_key: write_key,
+        }
+    }
+
+    /// Number of members emitted so far
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns `true` if no members were emitted
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Get JSON object with all the members, in order they were emitted in
+    pub fn finish(self) -> String {
+        let mut out = String::with_capacity(self.buf.len() + 2 * self.len());
+        out.push('{');
+        for i in 0..self.len() {
+            if i > 0 {
+                out.push(',');
+            }
+            self.write_member(&mut out, i);
+        }
+        out.push('}');
+        out
+    }
+
+    /// Get key of `i`-th member, as JSON string
+    #[doc(hidden)]
+    pub fn key(&self, i: usize) -> &str {
+        &self.buf[self.members[i].0..self.members[i].1]
+    }
+
+    #[doc(hidden)]
+    pub fn write_member(&self, out: &mut String, i: usize) {
+        let end = self
+            .members
+            .get(i + 1)
+            .map(|&(key, _)| key)
+            .unwrap_or_else(|| self.buf.len());
+        out.push_str(self.key(i));
+        out.push(':');
+        out.push_str(&self.buf[self.members[i].1..end]);
+    }
+
+    fn member<F>(&mut self, key: Key, value: F) -> Result
+    where
+        F: FnOnce(&mut String) -> Result,
+    {
+        let start = self.buf.len();
+        (self.write_key)(&mut self.buf, key_str(&key));
+        let value_start = self.buf.len();
+        match value(&mut self.buf) {
+            Ok(()) => {
+                self.members.push((start, value_start));
+                Ok(())
+            }
+            Err(e) => {
+                self.buf.truncate(start);
+                Err(e)
+            }
+        }
+    }
+}
+
+macro_rules! emit_number(
+    ($f:ident, $t:ty) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            self.member(key, |out| Ok(write!(out, "{}", val)?))
+        }
+    };
+);
+
+macro_rules! emit_float(
+    ($f:ident, $t:ty) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            self.member(key, |out| Ok(write_float!(out, val)?))
+        }
+    };
+);
+
+impl ::Serializer for Serializer {
+    emit_number!(emit_usize, usize);
+    emit_number!(emit_isize, isize);
+    emit_number!(emit_u8, u8);
+    emit_number!(emit_i8, i8);
+    emit_number!(emit_u16, u16);
+    emit_number!(emit_i16, i16);
+    emit_number!(emit_u32, u32);
+    emit_number!(emit_i32, i32);
+    emit_number!(emit_u64, u64);
+    emit_number!(emit_i64, i64);
+    emit_float!(emit_f32, f32);
+    emit_float!(emit_f64, f64);
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> Result {
+        self.member(key, |out| {
+            out.push_str(if val { "true" } else { "false" });
+            Ok(())
+        })
+    }
+
+    fn emit_char(&mut self, key: Key, val: char) -> Result {
+        self.member(key, |out| {
+            write_str(out, val.encode_utf8(&mut [0; 4]));
+            Ok(())
+        })
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> Result {
+        self.member(key, |out| {
+            write_str(out, val);
+            Ok(())
+        })
+    }
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        self.member(key, |out| {
+            out.push_str("null");
+            Ok(())
+        })
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        self.emit_unit(key)
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        self.member(key, |out| {
+            out.push('"');
+            Escaped(out).write_fmt(*val)?;
+            out.push('"');
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        self.member(key, |out| write_serde(out, value))
+    }
+}
+
+/// Write `value` as JSON
+#[cfg(feature = "nested-values")]
+#[doc(hidden)]
+pub fn write_serde(out: &mut String, value: &SerdeValue) -> Result {
+    ::serde::Serialize::serialize(
+        value.as_serde(),
+        nested::ValueSerializer(out),
+    )
+    .map_err(|_| Error::Other)
+}
+
+/// `Drain` writing records as JSON objects, one per line
+///
+/// Every object starts with the timestamp (RFC 3339, UTC), level and
+/// message, under keys `"ts"`, `"level"` and `"msg"` by default, followed by
+/// key-value pairs in `KeyOrder::Emitted` order, keeping duplicate keys. Key
+/// value pairs with keys of the timestamp, level or message are treated as
+/// their duplicates, so they are dropped unless duplicate keys are kept.
+///
+/// Every record is written with one `write_all` call, while holding a lock
+/// of the writer, and the writer is flushed by `Drain::flush` only, so
+/// wrapping it in `io::BufWriter` is a good idea for files.
+pub struct Json<W: io::Write> {
+    writer: Mutex<W>,
+    ts_key: Option<&'static str>,
+    level_key: &'static str,
+    msg_key: &'static str,
+    key_order: KeyOrder,
+    duplicate_keys: DuplicateKeys,
+}
+
+impl<W: io::Write> Json<W> {
+    /// Create `Json` writing to `writer`, with default settings
+    pub fn new(writer: W) -> Self {
+        Json {
+            writer: Mutex::new(writer),
+            ts_key: Some("ts"),
+            level_key: "level",
+            msg_key: "msg",
+            key_order: KeyOrder::Emitted,
+            duplicate_keys: DuplicateKeys::Keep,
+        }
+    }
+
+    /// Set key of the timestamp, or `None` to not write it
+    pub fn ts_key(mut self, key: Option<&'static str>) -> Self {
+        self.ts_key = key;
+        self
+    }
+
+    /// Set key of the level
+    pub fn level_key(mut self, key: &'static str) -> Self {
+        self.level_key = key;
+        self
+    }
+
+    /// Set key of the message
+    pub fn msg_key(mut self, key: &'static str) -> Self {
+        self.msg_key = key;
+        self
+    }
+
+    /// Set order of key-value pairs
+    pub fn key_order(mut self, key_order: KeyOrder) -> Self {
+        self.key_order = key_order;
+        self
+    }
+
+    /// Set handling of duplicate keys
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+
+    /// Format `record` as a JSON object (without a newline)
+    pub fn format(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> Result<String> {
+        let mut serializer = Serializer::new();
+        record.kv().serialize(record, &mut serializer)?;
+        let logger_start = serializer.len();
+        logger_values.serialize(record, &mut serializer)?;
+
+        let mut out = String::with_capacity(serializer.buf.len() + 128);
+        out.push('{');
+        let mut reserved = Vec::with_capacity(3);
+        if let Some(ts_key) = self.ts_key {
+            write_str(&mut out, ts_key);
+            write!(out, ":\"{}\",", Rfc3339(SystemTime::now()))?;
+            reserved.push(ts_key);
+        }
+        write_str(&mut out, self.level_key);
+        out.push(':');
+        write_str(&mut out, record.level().as_str());
+        out.push(',');
+        write_str(&mut out, self.msg_key);
+        out.push_str(":\"");
+        Escaped(&mut out).write_fmt(*record.msg())?;
+        out.push('"');
+        reserved.push(self.level_key);
+        reserved.push(self.msg_key);
+
+        for i in self.members(&serializer, logger_start, &reserved) {
+            out.push(',');
+            serializer.write_member(&mut out, i);
+        }
+        out.push('}');
+        Ok(out)
+    }
+
+    /// Indices of members of `serializer` to write, in order
+    fn members(
+        &self,
+        serializer: &Serializer,
+        logger_start: usize,
+        reserved: &[&str],
+    ) -> Vec<usize> {
+        let mut members: Vec<usize> = match self.duplicate_keys {
+            DuplicateKeys::Keep => (0..serializer.len()).collect(),
+            DuplicateKeys::First | DuplicateKeys::Last => {
+                let mut seen: HashSet<String> = reserved
+                    .iter()
+                    .map(|key| {
+                        let mut quoted = String::new();
+                        write_str(&mut quoted, key);
+                        quoted
+                    })
+                    .collect();
+                let mut members: Vec<usize> = (0..serializer.len()).collect();
+                if self.duplicate_keys == DuplicateKeys::Last {
+                    members.reverse();
+                }
+                members.retain(|&i| seen.insert(serializer.key(i).into()));
+                members.sort();
+                members
+            }
+        };
+        match self.key_order {
+            KeyOrder::Emitted => {}
+            KeyOrder::LoggerFirst => {
+                members.sort_by_key(|&i| i < logger_start);
+            }
+            KeyOrder::Sorted => {
+                members
+                    .sort_by(|&a, &b| serializer.key(a).cmp(serializer.key(b)));
+            }
+        }
+        members
+    }
+
+    fn lock(&self) -> MutexGuard<W> {
+        // a panic while writing can leave at most a partial line behind
+        match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl<W: io::Write> Drain for Json<W> {
+    type Ok = ();
+    type Err = Error;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let mut line = self.format(record, logger_values)?;
+        line.push('\n');
+        self.lock().write_all(line.as_bytes())?;
+        Ok(())
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    fn flush(&self) -> Result {
+        Ok(self.lock().flush()?)
+    }
+    fn shutdown(&self) -> Result {
+        self.flush()
+    }
+}
+
+/// Writing `serde` values as nested JSON values
+#[cfg(feature = "nested-values")]
+mod nested {
+    use core::fmt::{self, Write};
+    use serde::ser::{self, Serialize};
+    use std::string::{String, ToString};
+
+    use super::write_str;
+
+    /// Error of serializing a `serde` value
+    #[derive(Debug)]
+    pub struct SerdeError(String);
+
+    impl fmt::Display for SerdeError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl ::std::error::Error for SerdeError {
+        fn description(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl ser::Error for SerdeError {
+        fn custom<T: fmt::Display>(msg: T) -> Self {
+            SerdeError(msg.to_string())
+        }
+    }
+
+    impl From<fmt::Error> for SerdeError {
+        fn from(e: fmt::Error) -> Self {
+            SerdeError(e.to_string())
+        }
+    }
+
+    type Result = ::core::result::Result<(), SerdeError>;
+
+    /// `serde::Serializer` writing a JSON value
+    pub struct ValueSerializer<'a>(pub &'a mut String);
+
+    macro_rules! serialize_number(
+        ($f:ident, $t:ty) => {
+            fn $f(self, v: $t) -> Result {
+                Ok(write!(self.0, "{}", v)?)
+            }
+        };
+    );
+
+    impl<'a> ser::Serializer for ValueSerializer<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        type SerializeSeq = Compound<'a>;
+        type SerializeTuple = Compound<'a>;
+        type SerializeTupleStruct = Compound<'a>;
+        type SerializeTupleVariant = Compound<'a>;
+        type SerializeMap = Compound<'a>;
+        type SerializeStruct = Compound<'a>;
+        type SerializeStructVariant = Compound<'a>;
+
+        serialize_number!(serialize_i8, i8);
+        serialize_number!(serialize_i16, i16);
+        serialize_number!(serialize_i32, i32);
+        serialize_number!(serialize_i64, i64);
+        serialize_number!(serialize_i128, i128);
+        serialize_number!(serialize_u8, u8);
+        serialize_number!(serialize_u16, u16);
+        serialize_number!(serialize_u32, u32);
+        serialize_number!(serialize_u64, u64);
+        serialize_number!(serialize_u128, u128);
+
+        fn serialize_f32(self, v: f32) -> Result {
+            Ok(write_float!(self.0, v)?)
+        }
+
+        fn serialize_f64(self, v: f64) -> Result {
+            Ok(write_float!(self.0, v)?)
+        }
+
+        fn serialize_bool(self, v: bool) -> Result {
+            self.0.push_str(if v { "true" } else { "false" });
+            Ok(())
+        }
+
+        fn serialize_char(self, v: char) -> Result {
+            write_str(self.0, v.encode_utf8(&mut [0; 4]));
+            Ok(())
+        }
+
+        fn serialize_str(self, v: &str) -> Result {
+            write_str(self.0, v);
+            Ok(())
+        }
+
+        fn serialize_bytes(self, v: &[u8]) -> Result {
+            let mut seq = Compound::new(self.0, "[", "]");
+            for b in v {
+                ser::SerializeSeq::serialize_element(&mut seq, b)?;
+            }
+            ser::SerializeSeq::end(seq)
+        }
+
+        fn serialize_none(self) -> Result {
+            self.serialize_unit()
+        }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result {
+            value.serialize(self)
+        }
+
+        fn serialize_unit(self) -> Result {
+            self.0.push_str("null");
+            Ok(())
+        }
+
+        fn serialize_unit_struct(self, _name: &'static str) -> Result {
+            self.serialize_unit()
+        }
+
+        fn serialize_unit_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+        ) -> Result {
+            self.serialize_str(variant)
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(
+            self,
+            _name: &'static str,
+            value: &T,
+        ) -> Result {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Result {
+            self.0.push('{');
+            write_str(self.0, variant);
+            self.0.push(':');
+            value.serialize(ValueSerializer(&mut *self.0))?;
+            self.0.push('}');
+            Ok(())
+        }
+
+        fn serialize_seq(
+            self,
+            _len: Option<usize>,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            Ok(Compound::new(self.0, "[", "]"))
+        }
+
+        fn serialize_tuple(
+            self,
+            _len: usize,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            Ok(Compound::new(self.0, "[", "]"))
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            _name: &'static str,
+            _len: usize,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            Ok(Compound::new(self.0, "[", "]"))
+        }
+
+        fn serialize_tuple_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            self.0.push('{');
+            write_str(self.0, variant);
+            Ok(Compound::new(self.0, ":[", "]}"))
+        }
+
+        fn serialize_map(
+            self,
+            _len: Option<usize>,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            Ok(Compound::new(self.0, "{", "}"))
+        }
+
+        fn serialize_struct(
+            self,
+            _name: &'static str,
+            _len: usize,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            Ok(Compound::new(self.0, "{", "}"))
+        }
+
+        fn serialize_struct_variant(
+            self,
+            _name: &'static str,
+            _variant_index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> ::core::result::Result<Compound<'a>, SerdeError> {
+            self.0.push('{');
+            write_str(self.0, variant);
+            Ok(Compound::new(self.0, ":{", "}}"))
+        }
+    }
+
+    /// Sequence or map being written, for all the compound `serde` types
+    pub struct Compound<'a> {
+        out: &'a mut String,
+        first: bool,
+        close: &'static str,
+    }
+
+    impl<'a> Compound<'a> {
+        fn new(out: &'a mut String, open: &str, close: &'static str) -> Self {
+            out.push_str(open);
+            Compound {
+                out,
+                first: true,
+                close,
+            }
+        }
+
+        fn comma(&mut self) {
+            if !self.first {
+                self.out.push(',');
+            }
+            self.first = false;
+        }
+
+        fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result {
+            self.comma();
+            value.serialize(ValueSerializer(&mut *self.out))
+        }
+
+        /// Write key of a map entry; JSON keys can only be strings, so
+        /// other keys are written as strings of their JSON form
+        fn key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result {
+            let mut json = String::new();
+            key.serialize(ValueSerializer(&mut json))?;
+            self.comma();
+            if json.starts_with('"') {
+                self.out.push_str(&json);
+            } else {
+                write_str(self.out, &json);
+            }
+            self.out.push(':');
+            Ok(())
+        }
+
+        fn field<T: ?Sized + Serialize>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result {
+            self.element(key)?;
+            self.out.push(':');
+            value.serialize(ValueSerializer(&mut *self.out))
+        }
+
+        fn close(self) -> Result {
+            self.out.push_str(self.close);
+            Ok(())
+        }
+    }
+
+    impl<'a> ser::SerializeSeq for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_element<T: ?Sized + Serialize>(
+            &mut self,
+            value: &T,
+        ) -> Result {
+            self.element(value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeTuple for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_element<T: ?Sized + Serialize>(
+            &mut self,
+            value: &T,
+        ) -> Result {
+            self.element(value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            value: &T,
+        ) -> Result {
+            self.element(value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            value: &T,
+        ) -> Result {
+            self.element(value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeMap for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result {
+            self.key(key)
+        }
+        fn serialize_value<T: ?Sized + Serialize>(
+            &mut self,
+            value: &T,
+        ) -> Result {
+            value.serialize(ValueSerializer(&mut *self.out))
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeStruct for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result {
+            self.field(key, value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+
+    impl<'a> ser::SerializeStructVariant for Compound<'a> {
+        type Ok = ();
+        type Error = SerdeError;
+        fn serialize_field<T: ?Sized + Serialize>(
+            &mut self,
+            key: &'static str,
+            value: &T,
+        ) -> Result {
+            self.field(key, value)
+        }
+        fn end(self) -> Result {
+            self.close()
+        }
+    }
+}
@@ -8,3 +8,71 @@ pub use self::dynamic::Key;
 mod static_;
 #[cfg(not(feature = "dynamic-keys"))]
 pub use self::static_::Key;
+
+#[cfg(feature = "std")]
+use std::string::String;
+
+/// View `key` as `str`
+#[cfg(feature = "dynamic-keys")]
+pub fn key_str(key: &Key) -> &str {
+    key.as_str()
+}
+
+/// View `key` as `str`
+#[cfg(not(feature = "dynamic-keys"))]
+#[cfg_attr(clippy, allow(renamed_and_removed_lints, explicit_auto_deref))]
+pub fn key_str(key: &Key) -> &'static str {
+    *key
+}
+
+/// Create `Key` from a static string
+#[cfg(feature = "dynamic-keys")]
+pub fn key_from_static(key: &'static str) -> Key {
+    Key::from(key)
+}
+
+/// Create `Key` from a static string
+#[cfg(not(feature = "dynamic-keys"))]
+pub fn key_from_static(key: &'static str) -> Key {
+    key
+}
+
+/// Create `Key` from an owned string
+#[cfg(all(feature = "std", feature = "dynamic-keys"))]
+pub fn key_from_string(key: String) -> Key {
+    Key::from(key)
+}
+
+/// Create `Key` from an owned string
+///
+/// Static keys are `&'static str`, so the string is leaked; keys are interned
+/// in a process-wide table, so every distinct key is leaked only once.
+#[cfg(all(feature = "std", not(feature = "dynamic-keys")))]
+pub fn key_from_string(key: String) -> Key {
+    use std::boxed::Box;
+
+    let mut interned = match interned_keys().lock() {
+        Ok(interned) => interned,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    if let Some(&key) = interned.get(key.as_str()) {
+        return key;
+    }
+    // never freed, like all the interned keys
+    let key: &'static str = unsafe { &*Box::into_raw(key.into_boxed_str()) };
+    interned.insert(key);
+    key
+}
+
+/// Table of keys leaked by `key_from_string`
+#[cfg(all(feature = "std", not(feature = "dynamic-keys")))]
+fn interned_keys(
+) -> &'static ::std::sync::Mutex<::std::collections::HashSet<Key>> {
+    use lazy::Lazy;
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    static INTERNED: Lazy<Mutex<HashSet<Key>>> = lazy!();
+
+    INTERNED.get(|| Mutex::new(HashSet::new()))
+}
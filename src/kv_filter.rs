@@ -0,0 +1,204 @@
+use core::fmt::{self, Write};
+use core::result;
+use std::collections::HashSet;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use {Drain, Key, Level, OwnedKVList, Record, Result, Serializer, KV};
+
+/// Condition on a value of one key, used by `KVFilter`
+///
+/// Values are compared in their `Display` form (as emitted by
+/// `Serializer::emit_arguments`), so `"user_id" => 1234` and
+/// `"user_id" => "1234"` both match `KVCondition::Eq("1234".into())`.
+/// Numeric comparisons parse that form as `f64`, and never match values
+/// that are not numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KVCondition {
+    /// Key is present, with any value
+    Present,
+    /// Value is equal to given one
+    Eq(String),
+    /// Value is one of given ones
+    In(HashSet<String>),
+    /// Value is less than given number
+    Lt(f64),
+    /// Value is less than or equal to given number
+    Le(f64),
+    /// Value is greater than given number
+    Gt(f64),
+    /// Value is greater than or equal to given number
+    Ge(f64),
+}
+
+impl KVCondition {
+    /// Create `KVCondition::In` from any list of values
+    pub fn one_of<I, T>(values: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: fmt::Display,
+    {
+        KVCondition::In(values.into_iter().map(|v| v.to_string()).collect())
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        let number = || value.trim().parse::<f64>().ok();
+        match *self {
+            KVCondition::Present => true,
+            KVCondition::Eq(ref expected) => value == expected,
+            KVCondition::In(ref expected) => expected.contains(value),
+            KVCondition::Lt(x) => number().map(|n| n < x).unwrap_or(false),
+            KVCondition::Le(x) => number().map(|n| n <= x).unwrap_or(false),
+            KVCondition::Gt(x) => number().map(|n| n > x).unwrap_or(false),
+            KVCondition::Ge(x) => number().map(|n| n >= x).unwrap_or(false),
+        }
+    }
+}
+
+/// Serializer evaluating conditions on the first value of every key
+struct Evaluate<'a> {
+    conditions: &'a [(String, KVCondition)],
+    /// Result of every condition, once its key was found
+    results: Vec<Option<bool>>,
+    buf: String,
+}
+
+impl<'a> Serializer for Evaluate<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        let mut formatted = false;
+        for (i, &(ref name, ref condition)) in
+            self.conditions.iter().enumerate()
+        {
+            if self.results[i].is_some() || key != *name {
+                continue;
+            }
+            if !formatted {
+                self.buf.clear();
+                self.buf.write_fmt(*val)?;
+                formatted = true;
+            }
+            self.results[i] = Some(condition.matches(&self.buf));
+        }
+        Ok(())
+    }
+}
+
+/// `Drain` filtering records by their key-value pairs
+///
+/// Every condition added with `KVFilter::when` is checked against the value
+/// of its key, looked up in the record key-value pairs first, and then in the
+/// key-value pairs of the `Logger` (children before parents). Only the first
+/// value found is used, so a value of a record or child `Logger` overrides
+/// the one of a parent. A condition on a missing key doesn't match.
+///
+/// By default a record is passed when all the conditions match; use
+/// `KVFilter::any` to pass records matching at least one of them. Records at
+/// or above the level set with `KVFilter::always_pass` are passed without
+/// checking any conditions, so eg. debug output can be enabled only for one
+/// customer, while keeping the usual `Info` output for everyone.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = KVFilter::new(Discard)
+///     .when("tenant", KVCondition::one_of(&["a", "b"]))
+///     .when("user_id", KVCondition::Eq("1234".into()))
+///     .always_pass(Level::Info);
+/// # let _ = drain;
+/// ```
+pub struct KVFilter<D: Drain> {
+    drain: D,
+    conditions: Vec<(String, KVCondition)>,
+    any: bool,
+    always_pass: Option<Level>,
+}
+
+impl<D: Drain> KVFilter<D> {
+    /// Create `KVFilter` without any conditions
+    ///
+    /// Passes all the records, until a condition is added.
+    pub fn new(drain: D) -> Self {
+        KVFilter {
+            drain: drain,
+            conditions: vec![],
+            any: false,
+            always_pass: None,
+        }
+    }
+
+    /// Add a condition on the value of `key`
+    pub fn when(mut self, key: &str, condition: KVCondition) -> Self {
+        self.conditions.push((key.to_string(), condition));
+        self
+    }
+
+    /// Pass records matching any of the conditions, instead of all of them
+    pub fn any(mut self) -> Self {
+        self.any = true;
+        self
+    }
+
+    /// Pass records at or above `level` without checking the conditions
+    pub fn always_pass(mut self, level: Level) -> Self {
+        self.always_pass = Some(level);
+        self
+    }
+
+    fn matches(&self, record: &Record, logger_values: &OwnedKVList) -> bool {
+        if self.conditions.is_empty() {
+            return true;
+        }
+        let mut eval = Evaluate {
+            conditions: &self.conditions,
+            results: vec![None; self.conditions.len()],
+            buf: String::new(),
+        };
+        // values that fail to serialize are treated as missing
+        let _ = record
+            .kv()
+            .serialize(record, &mut eval)
+            .and_then(|_| logger_values.serialize(record, &mut eval));
+        let mut results = eval.results.iter().map(|r| r.unwrap_or(false));
+        if self.any {
+            results.any(|r| r)
+        } else {
+            results.all(|r| r)
+        }
+    }
+}
+
+impl<D: Drain> Drain for KVFilter<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let pass = match self.always_pass {
+            Some(level) if record.level().is_at_least(level) => true,
+            _ => self.matches(record, logger_values),
+        };
+        if pass {
+            Ok(Some(self.drain.log(record, logger_values)?))
+        } else {
+            Ok(None)
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::boxed::Box;
+
+/// Create an empty `Lazy`
+///
+/// Statics can't call functions on the oldest supported Rust, so this is a
+/// macro, usable in static initializers.
+macro_rules! lazy {
+    () => {
+        ::lazy::Lazy {
+            ptr: ::lazy::EMPTY,
+            marker: ::core::marker::PhantomData,
+        }
+    };
+}
+
+/// `Lazy::ptr` of an empty `Lazy`
+#[allow(deprecated)]
+#[cfg_attr(
+    clippy,
+    allow(renamed_and_removed_lints, declare_interior_mutable_const)
+)]
+pub const EMPTY: AtomicUsize = ::core::sync::atomic::ATOMIC_USIZE_INIT;
+
+/// Value created on first use
+///
+/// The only place in the crate creating values lazily. Statics can't have
+/// destructors on the oldest supported Rust, so `Lazy` never drops its value
+/// on its own: values of statics live until the process exits, and other
+/// owners free them with `take`.
+///
+/// Fields are public only for `lazy!`, and must not be used directly.
+pub struct Lazy<T> {
+    /// Address of the boxed value, or 0 if it wasn't created yet
+    pub ptr: AtomicUsize,
+    /// Makes `Lazy` `Send` and `Sync` only if `T` is
+    pub marker: PhantomData<T>,
+}
+
+impl<T> Lazy<T> {
+    /// Get the value, created with `init` if there's none yet
+    ///
+    /// Threads racing to create the value can all call `init`, but only one
+    /// of the values is kept; the others are dropped.
+    pub fn get<F>(&self, init: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        let mut ptr = self.ptr.load(Ordering::Acquire);
+        if ptr == 0 {
+            let new = Box::into_raw(Box::new(init())) as usize;
+            ptr = match self.ptr.compare_exchange(
+                0,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(current) => {
+                    // another thread was first
+                    drop(unsafe { Box::from_raw(new as *mut T) });
+                    current
+                }
+            };
+        }
+        // a non-zero `ptr` is always a value boxed above, and freed only by
+        // `take`, which can't run while `self` is borrowed
+        unsafe { &*(ptr as *const T) }
+    }
+
+    /// Take the value out, if it was created
+    pub fn take(&mut self) -> Option<T> {
+        match mem::replace(self.ptr.get_mut(), 0) {
+            0 => None,
+            // boxed by `get`, and not shared anymore
+            ptr => Some(*unsafe { Box::from_raw(ptr as *mut T) }),
+        }
+    }
+}
@@ -303,6 +303,23 @@
 #![cfg_attr(not(feature = "std"), feature(alloc))]
 #![cfg_attr(not(feature = "std"), feature(collections))]
 #![warn(missing_docs)]
+// the oldest supported Rust has no `dyn`, `'_`, match ergonomics, and
+// conversions of floats to bits
+#![allow(
+    unknown_lints,
+    bare_trait_objects,
+    mismatched_lifetime_syntaxes,
+    unnecessary_transmutes
+)]
+#![cfg_attr(
+    clippy,
+    allow(
+        renamed_and_removed_lints,
+        needless_borrowed_reference,
+        duration_subsec,
+        unnecessary_sort_by
+    )
+)]
 #![no_std]
 
 #[cfg(not(feature = "std"))]
@@ -313,8 +330,101 @@ extern crate collections;
 #[cfg(feature = "std")]
 extern crate std;
 
+#[macro_use]
+mod lazy;
 mod key;
 pub use self::key::Key;
+mod call_site;
+use self::call_site::InterestCache;
+pub use self::call_site::{call_sites, invalidate_interest, CallSite, CallSites};
+#[doc(hidden)]
+pub use self::call_site::{CallSiteState, CALL_SITE_STATE_INIT};
+mod sample;
+pub use self::sample::Sample;
+#[cfg(feature = "std")]
+mod rate_limit;
+#[cfg(feature = "std")]
+pub use self::rate_limit::RateLimit;
+#[cfg(feature = "std")]
+mod owned;
+#[cfg(feature = "std")]
+pub use self::owned::{OwnedRecord, OwnedValue};
+#[cfg(feature = "std")]
+mod dedup;
+#[cfg(feature = "std")]
+pub use self::dedup::Dedup;
+#[cfg(feature = "std")]
+mod flight_recorder;
+#[cfg(feature = "std")]
+pub use self::flight_recorder::FlightRecorder;
+#[cfg(feature = "std")]
+mod directives;
+#[cfg(feature = "std")]
+pub use self::directives::{DirectiveFilter, Directives, DirectivesError};
+#[cfg(feature = "std")]
+mod atomic_switch;
+#[cfg(feature = "std")]
+pub use self::atomic_switch::{AtomicSwitch, AtomicSwitchCtrl};
+#[cfg(feature = "std")]
+mod dynamic_level;
+#[cfg(feature = "std")]
+pub use self::dynamic_level::{DynamicLevel, DynamicLevelFilter};
+#[cfg(feature = "std")]
+mod router;
+#[cfg(feature = "std")]
+pub use self::router::Router;
+#[cfg(feature = "std")]
+mod broadcast;
+#[cfg(feature = "std")]
+pub use self::broadcast::{Broadcast, ErrorPolicy};
+#[cfg(feature = "std")]
+mod async_drain;
+#[cfg(feature = "std")]
+pub use self::async_drain::{Async, AsyncBuilder, AsyncError, OverflowPolicy};
+#[cfg(feature = "std")]
+mod kv_filter;
+#[cfg(feature = "std")]
+pub use self::kv_filter::{KVCondition, KVFilter};
+#[cfg(feature = "std")]
+mod redact;
+#[cfg(feature = "std")]
+pub use self::redact::{Redact, RedactSerializer, Redaction, Redactor};
+#[cfg(feature = "std")]
+mod rewrite_keys;
+#[cfg(feature = "std")]
+pub use self::rewrite_keys::{
+    KeyRewriter, Prefixed, RewriteKeys, RewriteSerializer,
+};
+#[cfg(feature = "std")]
+mod enrich;
+#[cfg(feature = "std")]
+pub use self::enrich::{
+    Enrich, Hostname, ProcessId, Provider, ThreadId, ThreadName, Timestamp,
+    Uptime,
+};
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+pub use self::stats::{Stats, StatsHandle, StatsSnapshot};
+#[cfg(feature = "std")]
+mod catch_unwind;
+#[cfg(feature = "std")]
+pub use self::catch_unwind::{CatchUnwind, CatchUnwindError};
+#[cfg(feature = "std")]
+#[macro_use]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod logfmt;
+#[cfg(feature = "std")]
+pub mod syslog;
+#[cfg(feature = "std")]
+mod deflate;
+#[cfg(feature = "std")]
+pub mod gelf;
+#[cfg(feature = "std")]
+pub mod binary;
 #[cfg(not(feature = "std"))]
 use alloc::arc::Arc;
 #[cfg(not(feature = "std"))]
@@ -326,6 +436,8 @@ use collections::string::String;
 
 #[cfg(feature = "nested-values")]
 extern crate erased_serde;
+#[cfg(feature = "nested-values")]
+extern crate serde;
 
 use core::{convert, fmt, result};
 use core::str::FromStr;
@@ -722,9 +834,19 @@ macro_rules! slog_record(
 #[macro_export]
 macro_rules! log(
     // `2` means that `;` was already found
-   (2 @ { $($fmt:tt)* }, { $($kv:tt)* },  $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr) => {
-      $l.log(&record!($lvl, $tag, &format_args!($msg_fmt, $($fmt)*), b!($($kv)*)))
-   };
+   (2 @ { $($fmt:tt)* }, { $($kv:tt)* },  $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr) => {{
+      static RS : $crate::RecordStatic<'static> = record_static!($lvl, $tag);
+      static CS : $crate::CallSite = $crate::CallSite {
+          record_static: &RS,
+          state: $crate::CALL_SITE_STATE_INIT,
+      };
+      // `$l` is evaluated once, but used as it is, so eg. `&Logger`
+      // still resolves to `Logger::log`, and not `Drain::log`
+      let logger = &$l;
+      if (*logger).is_interested(&CS) {
+          (*logger).log(&$crate::Record::new(&RS, &format_args!($msg_fmt, $($fmt)*), b!($($kv)*)))
+      }
+   }};
    (2 @ { $($fmt:tt)* }, { $($kv:tt)* }, $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr,) => {
        log!(2 @ { $($fmt)* }, { $($kv)* }, $l, $lvl, $tag, $msg_fmt)
    };
@@ -795,9 +917,19 @@ macro_rules! log(
 #[macro_export]
 macro_rules! slog_log(
     // `2` means that `;` was already found
-   (2 @ { $($fmt:tt)* }, { $($kv:tt)* },  $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr) => {
-      $l.log(&slog_record!($lvl, $tag, &format_args!($msg_fmt, $($fmt)*), slog_b!($($kv)*)))
-   };
+   (2 @ { $($fmt:tt)* }, { $($kv:tt)* },  $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr) => {{
+      static RS : $crate::RecordStatic<'static> = slog_record_static!($lvl, $tag);
+      static CS : $crate::CallSite = $crate::CallSite {
+          record_static: &RS,
+          state: $crate::CALL_SITE_STATE_INIT,
+      };
+      // `$l` is evaluated once, but used as it is, so eg. `&Logger`
+      // still resolves to `Logger::log`, and not `Drain::log`
+      let logger = &$l;
+      if (*logger).is_interested(&CS) {
+          (*logger).log(&$crate::Record::new(&RS, &format_args!($msg_fmt, $($fmt)*), slog_b!($($kv)*)))
+      }
+   }};
    (2 @ { $($fmt:tt)* }, { $($kv:tt)* }, $l:expr, $lvl:expr, $tag:expr, $msg_fmt:expr,) => {
        slog_log!(2 @ { $($fmt)* }, { $($kv)* }, $l, $lvl, $tag, $msg_fmt)
    };
@@ -1068,6 +1200,7 @@ where
 {
     drain: D,
     list: OwnedKVList,
+    interest: Option<Arc<InterestCache>>,
 }
 
 impl<D> Logger<D>
@@ -1105,10 +1238,12 @@ where
         D: 'static + SendSyncRefUnwindSafeDrain<Err = Never, Ok = ()>,
         T: SendSyncRefUnwindSafeKV + 'static,
     {
+        let interest = interest_cache(&drain);
         Logger {
             drain: Arc::new(drain)
                 as Arc<SendSyncRefUnwindSafeDrain<Ok = (), Err = Never>>,
             list: OwnedKVList::root(values),
+            interest: interest,
         }
     }
 
@@ -1131,6 +1266,7 @@ where
         T: SendSyncRefUnwindSafeKV + 'static,
     {
         Logger {
+            interest: interest_cache(&drain),
             drain: drain,
             list: OwnedKVList::root(values),
         }
@@ -1177,6 +1313,7 @@ where
         Logger {
             drain: self.drain.clone(),
             list: OwnedKVList::new(values, self.list.node.clone()),
+            interest: self.interest.clone(),
         }
     }
 
@@ -1189,6 +1326,35 @@ where
         let _ = self.drain.log(record, &self.list);
     }
 
+    /// Check if the drain is enabled for records of `call_site`
+    ///
+    /// Used by logging macros to skip building records the drain is not
+    /// enabled for. If the drain declares its interest static
+    /// (`Drain::is_interest_static`), `Drain::is_enabled` is asked once per
+    /// call site, and the answer is cached (for this `Logger` and all its
+    /// children) until `invalidate_interest` is called. Otherwise it's asked
+    /// every time.
+    ///
+    /// The cache of a root `Logger` is a table of 128 pointers to chunks of
+    /// 128 answers, allocated on first use, so answers for the first 16384
+    /// call sites registered in the process are cached; `is_enabled` is
+    /// always asked for any call sites registered after them.
+    #[inline]
+    pub fn is_interested(&self, call_site: &'static CallSite) -> bool {
+        match self.interest {
+            Some(ref interest) => interest
+                .is_enabled(call_site, |level| self.drain.is_enabled(level)),
+            None => self.drain.is_enabled(call_site.level()),
+        }
+    }
+
+    /// Flush any output buffered by the drain of this `Logger`
+    ///
+    /// See `Drain::flush`.
+    pub fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+
     /// Get list of key-value pairs assigned to this `Logger`
     pub fn list(&self) -> &OwnedKVList {
         &self.list
@@ -1213,6 +1379,7 @@ where
             drain: Arc::new(self.drain)
                 as Arc<SendSyncRefUnwindSafeDrain<Ok = (), Err = Never>>,
             list: self.list,
+            interest: self.interest,
         }
     }
 
@@ -1239,6 +1406,15 @@ where
     }
 }
 
+/// Interest cache of a root `Logger` with `drain`, if it can be cached
+fn interest_cache<D: Drain>(drain: &D) -> Option<Arc<InterestCache>> {
+    if drain.is_interest_static() {
+        Some(Arc::new(InterestCache::new()))
+    } else {
+        None
+    }
+}
+
 impl<D> Drain for Logger<D>
 where
     D: SendSyncUnwindSafeDrain<Ok = (), Err = Never>,
@@ -1264,6 +1440,18 @@ where
     fn is_enabled(&self, level: Level) -> bool {
         self.drain.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
 }
 
 // {{{ Drain
@@ -1327,6 +1515,11 @@ pub trait Drain {
     /// `log` will already do anyways (making decision if something should be
     /// logged or not).
     ///
+    /// Logging macros skip records this returns `false` for. If the drain
+    /// declares its answers static (see `Drain::is_interest_static`),
+    /// `Logger`s cache the answer per logging statement (see
+    /// `Logger::is_interested`).
+    ///
     /// ```
     /// # #[macro_use]
     /// # extern crate slog;
@@ -1345,6 +1538,47 @@ pub trait Drain {
         level.as_usize() <= ::__slog_static_max_level().as_usize()
     }
 
+    /// Check if answers of `is_enabled` can be cached
+    ///
+    /// `Logger`s cache `is_enabled` answers per logging statement only for
+    /// drains returning `true` here, asked once when the root `Logger` is
+    /// built; other drains are asked on every logging statement. A drain
+    /// returning `true`, that may start returning `true` from `is_enabled`
+    /// where it returned `false` (eg. after reconfiguration), must call
+    /// `invalidate_interest` afterwards.
+    ///
+    /// Drains wrapping other drains should return `true` only if all the
+    /// wrapped drains do. Default implementation returns `false`.
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        false
+    }
+
+    /// Flush any buffered output
+    ///
+    /// `Drain`s buffering records (in memory, or in a buffered writer) should
+    /// write them out to their destination. Wrapping `Drain`s should pass this
+    /// call to the `Drain`s they wrap. Useful before eg. `process::exit`, which
+    /// does not run destructors.
+    ///
+    /// Default implementation does nothing.
+    #[inline]
+    fn flush(&self) -> Result {
+        Ok(())
+    }
+
+    /// Flush any buffered output and release resources
+    ///
+    /// After `shutdown`, the `Drain` might drop (or fail on) any further
+    /// records. Wrapping `Drain`s should pass this call to the `Drain`s they
+    /// wrap.
+    ///
+    /// Default implementation calls `flush`.
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.flush()
+    }
+
     /// **Avoid**: See `is_enabled`
     #[inline]
     fn is_critical_enabled(&self) -> bool {
@@ -1428,6 +1662,21 @@ pub trait Drain {
         LevelFilter(self, level)
     }
 
+    /// Pass through only a fraction of logging records
+    ///
+    /// Wrap `Self` in `Sample`
+    ///
+    /// This will convert `self` to a `Drain` that keeps all `Critical` and
+    /// `Error` `Record`s, and only `rate` (between `0.0` and `1.0`) fraction
+    /// of `Record`s of other levels. Use `Sample::level_rate` to set
+    /// different rates for different logging levels.
+    fn sample(self, rate: f64) -> Sample<Self>
+    where
+        Self: Sized,
+    {
+        Sample::new(self, rate)
+    }
+
     /// Map logging errors returned by this drain
     ///
     /// `f` is a closure that takes `Drain::Err` returned by a given
@@ -1477,6 +1726,18 @@ impl<'a, D: Drain + 'a> Drain for &'a D {
     fn is_enabled(&self, level: Level) -> bool {
         (**self).is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        (**self).is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        (**self).flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        (**self).shutdown()
+    }
 }
 
 impl<'a, D: Drain + 'a> Drain for &'a mut D {
@@ -1494,6 +1755,18 @@ impl<'a, D: Drain + 'a> Drain for &'a mut D {
     fn is_enabled(&self, level: Level) -> bool {
         (**self).is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        (**self).is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        (**self).flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        (**self).shutdown()
+    }
 }
 
 #[cfg(feature = "std")]
@@ -1662,6 +1935,18 @@ impl<D: Drain + ?Sized> Drain for Box<D> {
     fn is_enabled(&self, level: Level) -> bool {
         (**self).is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        (**self).is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        (**self).flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        (**self).shutdown()
+    }
 }
 
 impl<D: Drain + ?Sized> Drain for Arc<D> {
@@ -1678,6 +1963,18 @@ impl<D: Drain + ?Sized> Drain for Arc<D> {
     fn is_enabled(&self, level: Level) -> bool {
         (**self).is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        (**self).is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        (**self).flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        (**self).shutdown()
+    }
 }
 
 /// `Drain` discarding everything
@@ -1696,6 +1993,10 @@ impl Drain for Discard {
     fn is_enabled(&self, _1: Level) -> bool {
         false
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        true
+    }
 }
 
 /// `Drain` filtering records
@@ -1743,6 +2044,18 @@ where
          */
         self.0.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.0.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.0.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.0.shutdown()
+    }
 }
 
 /// `Drain` filtering records by `Record` logging level
@@ -1782,6 +2095,18 @@ impl<D: Drain> Drain for LevelFilter<D> {
     fn is_enabled(&self, level: Level) -> bool {
         level.is_at_least(self.1) && self.0.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.0.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.0.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.0.shutdown()
+    }
 }
 
 /// `Drain` mapping error returned by another `Drain`
@@ -1822,6 +2147,18 @@ impl<D: Drain, E> Drain for MapError<D, E> {
     fn is_enabled(&self, level: Level) -> bool {
         self.drain.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
 }
 
 /// `Drain` duplicating records into two other `Drain`s
@@ -1860,6 +2197,22 @@ impl<D1: Drain, D2: Drain> Drain for Duplicate<D1, D2> {
     fn is_enabled(&self, level: Level) -> bool {
         self.0.is_enabled(level) || self.1.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.0.is_interest_static() && self.1.is_interest_static()
+    }
+    /// Flush both drains, returning the first error
+    fn flush(&self) -> Result {
+        let res1 = self.0.flush();
+        let res2 = self.1.flush();
+        res1.and(res2)
+    }
+    /// Shut down both drains, returning the first error
+    fn shutdown(&self) -> Result {
+        let res1 = self.0.shutdown();
+        let res2 = self.1.shutdown();
+        res1.and(res2)
+    }
 }
 
 /// `Drain` panicking on error
@@ -1904,6 +2257,18 @@ where
     fn is_enabled(&self, level: Level) -> bool {
         self.0.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.0.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.0.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.0.shutdown()
+    }
 }
 
 /// `Drain` ignoring result
@@ -1939,6 +2304,24 @@ impl<D: Drain> Drain for IgnoreResult<D> {
     fn is_enabled(&self, level: Level) -> bool {
         self.drain.is_enabled(level)
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+
+    /// Flush the wrapped drain, ignoring any error
+    #[inline]
+    fn flush(&self) -> Result {
+        let _ = self.drain.flush();
+        Ok(())
+    }
+
+    /// Shut down the wrapped drain, ignoring any error
+    #[inline]
+    fn shutdown(&self) -> Result {
+        let _ = self.drain.shutdown();
+        Ok(())
+    }
 }
 
 /// Error returned by `Mutex<D : Drain>`
@@ -2025,6 +2408,16 @@ impl<D: Drain> Drain for std::sync::Mutex<D> {
     fn is_enabled(&self, level: Level) -> bool {
         self.lock().ok().map_or(true, |lock| lock.is_enabled(level))
     }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.lock().ok().map_or(false, |lock| lock.is_interest_static())
+    }
+    fn flush(&self) -> Result {
+        self.lock().map_err(|_| Error::Other)?.flush()
+    }
+    fn shutdown(&self) -> Result {
+        self.lock().map_err(|_| Error::Other)?.shutdown()
+    }
 }
 // }}}
 
@@ -3235,6 +3628,14 @@ impl OwnedKVList {
             }),
         }
     }
+
+    /// Returns `true` if both lists are the same node
+    #[cfg(feature = "std")]
+    fn ptr_eq(&self, other: &OwnedKVList) -> bool {
+        // `Arc::ptr_eq` is not available on the oldest supported Rust
+        &*self.node as *const _ as *const u8
+            == &*other.node as *const _ as *const u8
+    }
 }
 
 impl<T> convert::From<OwnedKV<T>> for OwnedKVList
@@ -3245,6 +3646,47 @@ where
         OwnedKVList::root(from)
     }
 }
+
+/// Last `OwnedKVList` wrapped by a drain, with its wrapper
+///
+/// Records logged through the same `Logger` share its `OwnedKVList`, so
+/// drains rewriting `Logger` values (like `Redact`) can wrap them once,
+/// instead of allocating a new wrapper for every record.
+#[cfg(feature = "std")]
+struct WrappedValues {
+    last: std::sync::Mutex<Option<(OwnedKVList, OwnedKVList)>>,
+}
+
+#[cfg(feature = "std")]
+impl WrappedValues {
+    fn new() -> Self {
+        WrappedValues {
+            last: std::sync::Mutex::new(None),
+        }
+    }
+
+    /// Get wrapper of `values`, wrapping them with `wrap` if they're not
+    /// the last ones
+    fn get<F>(&self, values: &OwnedKVList, wrap: F) -> OwnedKVList
+    where
+        F: FnOnce(&OwnedKVList) -> OwnedKVList,
+    {
+        // the lock is never held while calling the wrapped drain, so
+        // poisoning is not possible
+        let mut last = match self.last.lock() {
+            Ok(last) => last,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some((ref last_values, ref wrapped)) = *last {
+            if last_values.ptr_eq(values) {
+                return wrapped.clone();
+            }
+        }
+        let wrapped = wrap(values);
+        *last = Some((values.clone(), wrapped.clone()));
+        wrapped
+    }
+}
 // }}}
 
 // {{{ Error
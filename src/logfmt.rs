@@ -0,0 +1,312 @@
+//! logfmt output
+//!
+//! `Logfmt` is a `Drain` writing every record as one line of space separated
+//! `key=value` pairs: timestamp, level and message, followed by key-value
+//! pairs of the record and its `Logger`, eg.
+//!
+//! ```text
+//! ts=2018-03-28T12:34:56.789012Z level=INFO msg="request done" status=200 path=/users
+//! ```
+//!
+//! Values are quoted only if needed: when they are empty, or contain spaces,
+//! `=`, quotes, backslashes or control characters. Quotes, backslashes and
+//! control characters are escaped in quoted values. Numbers and booleans are
+//! never quoted, `None` is written as an empty value (`key=`), and `()` as
+//! `()`. With `nested-values` feature, `SerdeValue`s are written as (quoted)
+//! JSON.
+//!
+//! Keys can't be quoted, so spaces, `=`, quotes and control characters in
+//! them are replaced with `_`.
+
+use core::fmt::{self, Write as FmtWrite};
+use core::result;
+use std::io;
+use std::string::String;
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use enrich::Rfc3339;
+#[cfg(feature = "nested-values")]
+use json::write_serde;
+use key::key_str;
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {Drain, Error, Key, OwnedKVList, Record, Result, KV};
+
+/// Returns `true` if `s` has to be quoted
+fn needs_quotes(s: &str) -> bool {
+    s.is_empty()
+        || s.chars().any(|c| {
+            c == ' ' || c == '=' || c == '"' || c == '\\' || c.is_control()
+        })
+}
+
+/// Write `key`, with characters which would need quoting replaced with `_`
+#[doc(hidden)]
+pub fn write_key(out: &mut String, key: &str) {
+    for c in key.chars() {
+        if c == ' ' || c == '=' || c == '"' || c.is_control() {
+            out.push('_');
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+/// Write `s`, quoted if needed
+#[doc(hidden)]
+pub fn write_value(out: &mut String, s: &str) {
+    if !needs_quotes(s) {
+        out.push_str(s);
+        return;
+    }
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// `Serializer` rendering key-value pairs as a logfmt line (without a
+/// newline)
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::*;
+///
+/// fn main() {
+///     let mut serializer = logfmt::Serializer::new();
+///     serializer.emit_str("user".into(), "ana").unwrap();
+///     serializer.emit_str("query".into(), "a=\"b\"").unwrap();
+///     serializer.emit_f64("load".into(), 0.5).unwrap();
+///     serializer.emit_none("group".into()).unwrap();
+///     assert_eq!(
+///         serializer.finish(),
+///         r#"user=ana query="a=\"b\"" load=0.5 group="#
+///     );
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Serializer {
+    out: String,
+    /// Buffer for values which have to be formatted before quoting
+    scratch: String,
+}
+
+impl Serializer {
+    /// Create empty `Serializer`
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    /// Get the line
+    pub fn finish(self) -> String {
+        self.out
+    }
+
+    /// Write `key` and `=`, separated from the previous pair
+    fn key(&mut self, key: &str) {
+        if !self.out.is_empty() {
+            self.out.push(' ');
+        }
+        write_key(&mut self.out, key);
+        self.out.push('=');
+    }
+
+    fn write_str(&mut self, key: &str, val: &str) {
+        self.key(key);
+        write_value(&mut self.out, val);
+    }
+
+    fn write_args(&mut self, key: &str, val: &fmt::Arguments) -> Result {
+        self.scratch.clear();
+        self.scratch.write_fmt(*val)?;
+        self.key(key);
+        write_value(&mut self.out, &self.scratch);
+        Ok(())
+    }
+}
+
+macro_rules! emit_unquoted(
+    ($f:ident, $t:ty, $fmt:expr) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            self.key(key_str(&key));
+            Ok(write!(self.out, $fmt, val)?)
+        }
+    };
+);
+
+impl ::Serializer for Serializer {
+    emit_unquoted!(emit_usize, usize, "{}");
+    emit_unquoted!(emit_isize, isize, "{}");
+    emit_unquoted!(emit_bool, bool, "{}");
+    emit_unquoted!(emit_u8, u8, "{}");
+    emit_unquoted!(emit_i8, i8, "{}");
+    emit_unquoted!(emit_u16, u16, "{}");
+    emit_unquoted!(emit_i16, i16, "{}");
+    emit_unquoted!(emit_u32, u32, "{}");
+    emit_unquoted!(emit_i32, i32, "{}");
+    emit_unquoted!(emit_u64, u64, "{}");
+    emit_unquoted!(emit_i64, i64, "{}");
+    // `Debug` keeps the fraction (`1.0`), and uses exponents for very big or
+    // small numbers; NaN and infinities are `NaN`, `inf` and `-inf`
+    emit_unquoted!(emit_f32, f32, "{:?}");
+    emit_unquoted!(emit_f64, f64, "{:?}");
+
+    fn emit_char(&mut self, key: Key, val: char) -> Result {
+        self.write_str(key_str(&key), val.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> Result {
+        self.write_str(key_str(&key), val);
+        Ok(())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        self.write_str(key_str(&key), "()");
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        self.key(key_str(&key));
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        self.write_args(key_str(&key), val)
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        self.scratch.clear();
+        write_serde(&mut self.scratch, value)?;
+        self.key(key_str(&key));
+        write_value(&mut self.out, &self.scratch);
+        Ok(())
+    }
+}
+
+/// `Drain` writing records in logfmt, one per line
+///
+/// Every line starts with the timestamp (RFC 3339, UTC), level and message,
+/// under keys `ts`, `level` and `msg` by default, followed by key-value
+/// pairs of the record, and then of its `Logger` (children before parents).
+///
+/// Every record is written with one `write_all` call, while holding a lock
+/// of the writer, and the writer is flushed by `Drain::flush` only, so
+/// wrapping it in `io::BufWriter` is a good idea for files.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::logfmt::Logfmt;
+/// use slog::*;
+///
+/// fn main() {
+///     let drain = Logfmt::new(std::io::stderr()).ts_key(None);
+///     let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+///     info!(log, "started"; "port" => 8080);
+/// }
+/// ```
+pub struct Logfmt<W: io::Write> {
+    writer: Mutex<W>,
+    ts_key: Option<&'static str>,
+    level_key: &'static str,
+    msg_key: &'static str,
+}
+
+impl<W: io::Write> Logfmt<W> {
+    /// Create `Logfmt` writing to `writer`, with default settings
+    pub fn new(writer: W) -> Self {
+        Logfmt {
+            writer: Mutex::new(writer),
+            ts_key: Some("ts"),
+            level_key: "level",
+            msg_key: "msg",
+        }
+    }
+
+    /// Set key of the timestamp, or `None` to not write it
+    pub fn ts_key(mut self, key: Option<&'static str>) -> Self {
+        self.ts_key = key;
+        self
+    }
+
+    /// Set key of the level
+    pub fn level_key(mut self, key: &'static str) -> Self {
+        self.level_key = key;
+        self
+    }
+
+    /// Set key of the message
+    pub fn msg_key(mut self, key: &'static str) -> Self {
+        self.msg_key = key;
+        self
+    }
+
+    /// Format `record` as a logfmt line (without a newline)
+    pub fn format(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> Result<String> {
+        let mut serializer = Serializer::new();
+        if let Some(ts_key) = self.ts_key {
+            serializer.write_args(
+                ts_key,
+                &format_args!("{}", Rfc3339(SystemTime::now())),
+            )?;
+        }
+        serializer.write_str(self.level_key, record.level().as_str());
+        serializer.write_args(self.msg_key, record.msg())?;
+        record.kv().serialize(record, &mut serializer)?;
+        logger_values.serialize(record, &mut serializer)?;
+        Ok(serializer.finish())
+    }
+
+    fn lock(&self) -> MutexGuard<W> {
+        // a panic while writing can leave at most a partial line behind
+        match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl<W: io::Write> Drain for Logfmt<W> {
+    type Ok = ();
+    type Err = Error;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let mut line = self.format(record, logger_values)?;
+        line.push('\n');
+        self.lock().write_all(line.as_bytes())?;
+        Ok(())
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    fn flush(&self) -> Result {
+        Ok(self.lock().flush()?)
+    }
+    fn shutdown(&self) -> Result {
+        self.flush()
+    }
+}
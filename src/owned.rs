@@ -0,0 +1,437 @@
+use core::fmt;
+#[cfg(feature = "nested-values")]
+use std::boxed::Box;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+#[cfg(feature = "nested-values")]
+use key::key_from_static;
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {
+    BorrowedKV, Drain, Key, Level, OwnedKVList, Record, RecordLocation,
+    RecordStatic, Result, Serializer, Value, KV,
+};
+
+/// Owned, typed copy of a value emitted to a `Serializer`
+///
+/// There's a variant for every `Serializer::emit_*` method, so a value
+/// serialized into `OwnedValue` will be emitted exactly the same way when
+/// serialized again.
+pub enum OwnedValue {
+    /// `usize`
+    Usize(usize),
+    /// `isize`
+    Isize(isize),
+    /// `bool`
+    Bool(bool),
+    /// `char`
+    Char(char),
+    /// `u8`
+    U8(u8),
+    /// `i8`
+    I8(i8),
+    /// `u16`
+    U16(u16),
+    /// `i16`
+    I16(i16),
+    /// `u32`
+    U32(u32),
+    /// `i32`
+    I32(i32),
+    /// `f32`
+    F32(f32),
+    /// `u64`
+    U64(u64),
+    /// `i64`
+    I64(i64),
+    /// `f64`
+    F64(f64),
+    /// `str`
+    Str(String),
+    /// `()`
+    Unit,
+    /// `None`
+    None,
+    /// `fmt::Arguments` (already formatted)
+    Arguments(String),
+    /// `SerdeValue`
+    #[cfg(feature = "nested-values")]
+    Serde(Box<SerdeValue + Send>),
+}
+
+impl Clone for OwnedValue {
+    fn clone(&self) -> Self {
+        match *self {
+            OwnedValue::Usize(v) => OwnedValue::Usize(v),
+            OwnedValue::Isize(v) => OwnedValue::Isize(v),
+            OwnedValue::Bool(v) => OwnedValue::Bool(v),
+            OwnedValue::Char(v) => OwnedValue::Char(v),
+            OwnedValue::U8(v) => OwnedValue::U8(v),
+            OwnedValue::I8(v) => OwnedValue::I8(v),
+            OwnedValue::U16(v) => OwnedValue::U16(v),
+            OwnedValue::I16(v) => OwnedValue::I16(v),
+            OwnedValue::U32(v) => OwnedValue::U32(v),
+            OwnedValue::I32(v) => OwnedValue::I32(v),
+            OwnedValue::F32(v) => OwnedValue::F32(v),
+            OwnedValue::U64(v) => OwnedValue::U64(v),
+            OwnedValue::I64(v) => OwnedValue::I64(v),
+            OwnedValue::F64(v) => OwnedValue::F64(v),
+            OwnedValue::Str(ref v) => OwnedValue::Str(v.clone()),
+            OwnedValue::Unit => OwnedValue::Unit,
+            OwnedValue::None => OwnedValue::None,
+            OwnedValue::Arguments(ref v) => OwnedValue::Arguments(v.clone()),
+            #[cfg(feature = "nested-values")]
+            OwnedValue::Serde(ref v) => OwnedValue::Serde(v.to_sendable()),
+        }
+    }
+}
+
+/// Values are equal if they have the same type and value
+///
+/// `SerdeValue`s are opaque, and never equal to anything.
+impl PartialEq for OwnedValue {
+    fn eq(&self, other: &OwnedValue) -> bool {
+        match (self, other) {
+            (&OwnedValue::Usize(ref a), &OwnedValue::Usize(ref b)) => a == b,
+            (&OwnedValue::Isize(ref a), &OwnedValue::Isize(ref b)) => a == b,
+            (&OwnedValue::Bool(ref a), &OwnedValue::Bool(ref b)) => a == b,
+            (&OwnedValue::Char(ref a), &OwnedValue::Char(ref b)) => a == b,
+            (&OwnedValue::U8(ref a), &OwnedValue::U8(ref b)) => a == b,
+            (&OwnedValue::I8(ref a), &OwnedValue::I8(ref b)) => a == b,
+            (&OwnedValue::U16(ref a), &OwnedValue::U16(ref b)) => a == b,
+            (&OwnedValue::I16(ref a), &OwnedValue::I16(ref b)) => a == b,
+            (&OwnedValue::U32(ref a), &OwnedValue::U32(ref b)) => a == b,
+            (&OwnedValue::I32(ref a), &OwnedValue::I32(ref b)) => a == b,
+            (&OwnedValue::F32(ref a), &OwnedValue::F32(ref b)) => a == b,
+            (&OwnedValue::U64(ref a), &OwnedValue::U64(ref b)) => a == b,
+            (&OwnedValue::I64(ref a), &OwnedValue::I64(ref b)) => a == b,
+            (&OwnedValue::F64(ref a), &OwnedValue::F64(ref b)) => a == b,
+            (&OwnedValue::Str(ref a), &OwnedValue::Str(ref b)) => a == b,
+            (&OwnedValue::Unit, &OwnedValue::Unit) => true,
+            (&OwnedValue::None, &OwnedValue::None) => true,
+            (&OwnedValue::Arguments(ref a), &OwnedValue::Arguments(ref b)) => {
+                a == b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for OwnedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OwnedValue::Usize(v) => write!(f, "Usize({})", v),
+            OwnedValue::Isize(v) => write!(f, "Isize({})", v),
+            OwnedValue::Bool(v) => write!(f, "Bool({})", v),
+            OwnedValue::Char(v) => write!(f, "Char({:?})", v),
+            OwnedValue::U8(v) => write!(f, "U8({})", v),
+            OwnedValue::I8(v) => write!(f, "I8({})", v),
+            OwnedValue::U16(v) => write!(f, "U16({})", v),
+            OwnedValue::I16(v) => write!(f, "I16({})", v),
+            OwnedValue::U32(v) => write!(f, "U32({})", v),
+            OwnedValue::I32(v) => write!(f, "I32({})", v),
+            OwnedValue::F32(v) => write!(f, "F32({:?})", v),
+            OwnedValue::U64(v) => write!(f, "U64({})", v),
+            OwnedValue::I64(v) => write!(f, "I64({})", v),
+            OwnedValue::F64(v) => write!(f, "F64({:?})", v),
+            OwnedValue::Str(ref v) => write!(f, "Str({:?})", v),
+            OwnedValue::Unit => write!(f, "Unit"),
+            OwnedValue::None => write!(f, "None"),
+            OwnedValue::Arguments(ref v) => write!(f, "Arguments({:?})", v),
+            #[cfg(feature = "nested-values")]
+            OwnedValue::Serde(_) => write!(f, "Serde(..)"),
+        }
+    }
+}
+
+/// Formats the value the way `Serializer` does by default, eg. `()` for
+/// `Unit`, and nothing for `None`
+///
+/// `SerdeValue`s are formatted with `SerdeValue::serialize_fallback`, or as
+/// `[serde]` if they don't support it.
+impl fmt::Display for OwnedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OwnedValue::Usize(v) => write!(f, "{}", v),
+            OwnedValue::Isize(v) => write!(f, "{}", v),
+            OwnedValue::Bool(v) => write!(f, "{}", v),
+            OwnedValue::Char(v) => write!(f, "{}", v),
+            OwnedValue::U8(v) => write!(f, "{}", v),
+            OwnedValue::I8(v) => write!(f, "{}", v),
+            OwnedValue::U16(v) => write!(f, "{}", v),
+            OwnedValue::I16(v) => write!(f, "{}", v),
+            OwnedValue::U32(v) => write!(f, "{}", v),
+            OwnedValue::I32(v) => write!(f, "{}", v),
+            OwnedValue::F32(v) => write!(f, "{}", v),
+            OwnedValue::U64(v) => write!(f, "{}", v),
+            OwnedValue::I64(v) => write!(f, "{}", v),
+            OwnedValue::F64(v) => write!(f, "{}", v),
+            OwnedValue::Str(ref v) => write!(f, "{}", v),
+            OwnedValue::Unit => write!(f, "()"),
+            OwnedValue::None => Ok(()),
+            OwnedValue::Arguments(ref v) => write!(f, "{}", v),
+            #[cfg(feature = "nested-values")]
+            OwnedValue::Serde(ref v) => {
+                let mut buf = String::new();
+                match v.serialize_fallback(
+                    key_from_static(""),
+                    &mut WriteValues(&mut buf),
+                ) {
+                    Ok(()) => write!(f, "{}", buf),
+                    Err(_) => write!(f, "[serde]"),
+                }
+            }
+        }
+    }
+}
+
+/// `Serializer` writing just the values, in their default form
+#[cfg(feature = "nested-values")]
+struct WriteValues<'a>(&'a mut String);
+
+#[cfg(feature = "nested-values")]
+impl<'a> Serializer for WriteValues<'a> {
+    fn emit_arguments(&mut self, _: Key, val: &fmt::Arguments) -> Result {
+        use core::fmt::Write;
+        self.0.write_fmt(*val)?;
+        Ok(())
+    }
+}
+
+impl Value for OwnedValue {
+    fn serialize(
+        &self,
+        _record: &Record,
+        key: Key,
+        serializer: &mut Serializer,
+    ) -> Result {
+        match *self {
+            OwnedValue::Usize(v) => serializer.emit_usize(key, v),
+            OwnedValue::Isize(v) => serializer.emit_isize(key, v),
+            OwnedValue::Bool(v) => serializer.emit_bool(key, v),
+            OwnedValue::Char(v) => serializer.emit_char(key, v),
+            OwnedValue::U8(v) => serializer.emit_u8(key, v),
+            OwnedValue::I8(v) => serializer.emit_i8(key, v),
+            OwnedValue::U16(v) => serializer.emit_u16(key, v),
+            OwnedValue::I16(v) => serializer.emit_i16(key, v),
+            OwnedValue::U32(v) => serializer.emit_u32(key, v),
+            OwnedValue::I32(v) => serializer.emit_i32(key, v),
+            OwnedValue::F32(v) => serializer.emit_f32(key, v),
+            OwnedValue::U64(v) => serializer.emit_u64(key, v),
+            OwnedValue::I64(v) => serializer.emit_i64(key, v),
+            OwnedValue::F64(v) => serializer.emit_f64(key, v),
+            OwnedValue::Str(ref v) => serializer.emit_str(key, v),
+            OwnedValue::Unit => serializer.emit_unit(key),
+            OwnedValue::None => serializer.emit_none(key),
+            OwnedValue::Arguments(ref v) => {
+                serializer.emit_arguments(key, &format_args!("{}", v))
+            }
+            #[cfg(feature = "nested-values")]
+            OwnedValue::Serde(ref v) => serializer.emit_serde(key, &**v),
+        }
+    }
+}
+
+macro_rules! impl_collect {
+    ($t:ty, $f:ident, $v:ident) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            self.0.push((key, OwnedValue::$v(val)));
+            Ok(())
+        }
+    };
+}
+
+/// `Serializer` collecting owned copies of key-value pairs
+struct Collect<'a>(&'a mut Vec<(Key, OwnedValue)>);
+
+impl<'a> Serializer for Collect<'a> {
+    impl_collect!(usize, emit_usize, Usize);
+    impl_collect!(isize, emit_isize, Isize);
+    impl_collect!(bool, emit_bool, Bool);
+    impl_collect!(char, emit_char, Char);
+    impl_collect!(u8, emit_u8, U8);
+    impl_collect!(i8, emit_i8, I8);
+    impl_collect!(u16, emit_u16, U16);
+    impl_collect!(i16, emit_i16, I16);
+    impl_collect!(u32, emit_u32, U32);
+    impl_collect!(i32, emit_i32, I32);
+    impl_collect!(f32, emit_f32, F32);
+    impl_collect!(u64, emit_u64, U64);
+    impl_collect!(i64, emit_i64, I64);
+    impl_collect!(f64, emit_f64, F64);
+
+    fn emit_str(&mut self, key: Key, val: &str) -> Result {
+        self.0.push((key, OwnedValue::Str(val.to_string())));
+        Ok(())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        self.0.push((key, OwnedValue::Unit));
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        self.0.push((key, OwnedValue::None));
+        Ok(())
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        self.0.push((key, OwnedValue::Arguments(val.to_string())));
+        Ok(())
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        self.0.push((key, OwnedValue::Serde(value.to_sendable())));
+        Ok(())
+    }
+}
+
+/// Serialize `kv` into a list of owned key-value pairs
+///
+/// Pairs are kept in the order they were emitted in.
+pub fn serialize_kv<K: KV + ?Sized>(
+    record: &Record,
+    kv: &K,
+) -> Result<Vec<(Key, OwnedValue)>> {
+    let mut pairs = Vec::new();
+    kv.serialize(record, &mut Collect(&mut pairs))?;
+    Ok(pairs)
+}
+
+/// `KV` emitting owned key-value pairs
+struct OwnedPairs<'a>(&'a [(Key, OwnedValue)]);
+
+impl<'a> KV for OwnedPairs<'a> {
+    fn serialize(
+        &self,
+        record: &Record,
+        serializer: &mut Serializer,
+    ) -> Result {
+        for &(ref key, ref value) in self.0 {
+            value.serialize(record, Key::clone(key), serializer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Logging `Record` with all its data owned
+///
+/// `Record<'a>` only borrows its data, so it can't outlive the logging
+/// statement that created it. `OwnedRecord` copies the message (formatted),
+/// code location, tag and key-value pairs of the `Record` (serialized into
+/// `OwnedValue`s), and keeps a reference to the `OwnedKVList` of the `Logger`
+/// it was logged with. It is `Send`, so it can be buffered, or sent to
+/// another thread, and later passed to a `Drain` just like the original.
+#[derive(Clone)]
+pub struct OwnedRecord {
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    msg: String,
+    kv: Vec<(Key, OwnedValue)>,
+    logger_values: OwnedKVList,
+}
+
+impl OwnedRecord {
+    /// Create an owned copy of `record` logged with `logger_values`
+    ///
+    /// Fails if serializing record key-value pairs fails.
+    pub fn new(record: &Record, logger_values: &OwnedKVList) -> Result<Self> {
+        Ok(OwnedRecord {
+            location: *record.location(),
+            tag: record.tag().to_string(),
+            level: record.level(),
+            msg: record.msg().to_string(),
+            kv: serialize_kv(record, &record.kv())?,
+            logger_values: logger_values.clone(),
+        })
+    }
+
+    /// Get record logging level
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Get formatted message
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// Get tag
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Get code location
+    pub fn location(&self) -> &RecordLocation {
+        &self.location
+    }
+
+    /// Get record key-value pairs
+    pub fn kv(&self) -> &[(Key, OwnedValue)] {
+        &self.kv
+    }
+
+    /// Get key-value pairs of the `Logger` the record was logged with
+    pub fn logger_values(&self) -> &OwnedKVList {
+        &self.logger_values
+    }
+
+    /// Serialize `Logger` key-value pairs into owned key-value pairs
+    pub fn serialize_logger_values(&self) -> Result<Vec<(Key, OwnedValue)>> {
+        self.as_record(|record| serialize_kv(record, &self.logger_values))
+    }
+
+    /// Call `f` with a `Record` borrowing data of `self`
+    pub fn as_record<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&Record) -> R,
+    {
+        self.as_record_with(&(), f)
+    }
+
+    /// Call `f` with a `Record` borrowing data of `self`, with additional
+    /// key-value pairs `extra` emitted before the recorded ones
+    pub fn as_record_with<K, F, R>(&self, extra: &K, f: F) -> R
+    where
+        K: KV,
+        F: FnOnce(&Record) -> R,
+    {
+        let rs = RecordStatic {
+            location: &self.location,
+            tag: &self.tag,
+            level: self.level,
+        };
+        let kv = (extra, OwnedPairs(&self.kv));
+        f(&Record::new(
+            &rs,
+            &format_args!("{}", self.msg),
+            BorrowedKV(&kv),
+        ))
+    }
+
+    /// Pass the record to `drain`, with `Logger` key-value pairs it was
+    /// logged with
+    pub fn log_to<D: Drain + ?Sized>(
+        &self,
+        drain: &D,
+    ) -> ::core::result::Result<D::Ok, D::Err> {
+        self.as_record(|record| drain.log(record, &self.logger_values))
+    }
+}
+
+impl fmt::Debug for OwnedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedRecord")
+            .field("level", &self.level)
+            .field("msg", &self.msg)
+            .field("tag", &self.tag)
+            .field("file", &self.location.file)
+            .field("line", &self.location.line)
+            .field("kv", &self.kv)
+            .field("logger_values", &self.logger_values)
+            .finish()
+    }
+}
@@ -0,0 +1,376 @@
+use core::{cmp, iter, result};
+use std::collections::{hash_map, HashMap};
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+use {
+    BorrowedKV, Drain, Level, OwnedKVList, Record, RecordLocation,
+    RecordStatic, Result, SingleKV,
+};
+
+/// Token bucket of a single call site (or tag)
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+    suppressed: usize,
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    values: Option<OwnedKVList>,
+}
+
+impl Bucket {
+    fn new(record: &Record, burst: f64) -> Self {
+        Bucket {
+            tokens: burst,
+            last: Instant::now(),
+            suppressed: 0,
+            location: *record.location(),
+            tag: record.tag().to_string(),
+            level: record.level(),
+            values: None,
+        }
+    }
+
+    fn refill(&mut self, per_second: f64, burst: f64) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        let elapsed = elapsed.as_secs() as f64
+            + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+        self.tokens = (self.tokens + elapsed * per_second).min(burst);
+        self.last = now;
+    }
+
+    /// Time the bucket will have a token again, if ever
+    fn due(&self, per_second: f64) -> Option<Instant> {
+        if per_second <= 0.0 {
+            return None;
+        }
+        // capped at a few years, so it fits in `Duration`
+        let wait = ((1.0 - self.tokens).max(0.0) / per_second).min(1e8);
+        let secs = wait.trunc();
+        let nanos = ((wait - secs) * 1_000_000_000.0) as u32;
+        Some(self.last + Duration::new(secs as u64, nanos))
+    }
+
+    fn take_summary(&mut self, by_tag: bool) -> Option<Summary> {
+        if self.suppressed == 0 {
+            return None;
+        }
+        let summary = Summary {
+            count: self.suppressed,
+            location: self.location,
+            tag: self.tag.clone(),
+            level: self.level,
+            values: self.values.take(),
+            by_tag: by_tag,
+        };
+        self.suppressed = 0;
+        Some(summary)
+    }
+}
+
+/// Record describing suppressed records of one call site
+struct Summary {
+    count: usize,
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    values: Option<OwnedKVList>,
+    /// Records were suppressed by tag, not by call site
+    by_tag: bool,
+}
+
+impl Summary {
+    fn log<D: Drain>(
+        &self,
+        drain: &D,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<D::Ok, D::Err> {
+        let rs = RecordStatic {
+            location: &self.location,
+            tag: &self.tag,
+            level: self.level,
+        };
+        let values = self.values.as_ref().unwrap_or(logger_values);
+        let kv = SingleKV::from(("suppressed", self.count));
+        if self.by_tag {
+            drain.log(
+                &Record::new(
+                    &rs,
+                    &format_args!(
+                        "suppressed {} records tagged {:?}",
+                        self.count, self.tag
+                    ),
+                    BorrowedKV(&kv),
+                ),
+                values,
+            )
+        } else {
+            drain.log(
+                &Record::new(
+                    &rs,
+                    &format_args!(
+                        "suppressed {} records from {}:{}",
+                        self.count, self.location.file, self.location.line
+                    ),
+                    BorrowedKV(&kv),
+                ),
+                values,
+            )
+        }
+    }
+}
+
+/// Iterator over all the buckets, returned by `Buckets::all`
+type AllBuckets<'a> = iter::Chain<
+    hash_map::ValuesMut<'a, (&'static str, u32, u32), Bucket>,
+    hash_map::ValuesMut<'a, String, Bucket>,
+>;
+
+struct Buckets {
+    by_location: HashMap<(&'static str, u32, u32), Bucket>,
+    by_tag: HashMap<String, Bucket>,
+    /// Earliest time a bucket with suppressed records has a token again
+    next_due: Option<Instant>,
+}
+
+impl Buckets {
+    fn all(&mut self) -> AllBuckets {
+        self.by_location
+            .values_mut()
+            .chain(self.by_tag.values_mut())
+    }
+
+    /// Take summaries of all the buckets with suppressed records which have
+    /// a token again
+    fn take_due_summaries(
+        &mut self,
+        per_second: f64,
+        burst: f64,
+        by_tag: bool,
+        summaries: &mut Vec<Summary>,
+    ) {
+        let now = Instant::now();
+        match self.next_due {
+            Some(due) if due <= now => {}
+            _ => return,
+        }
+        let mut next_due = None;
+        for bucket in self.all().filter(|bucket| bucket.suppressed > 0) {
+            bucket.refill(per_second, burst);
+            if bucket.tokens >= 1.0 {
+                summaries.extend(bucket.take_summary(by_tag));
+            } else {
+                next_due = earliest(next_due, bucket.due(per_second));
+            }
+        }
+        self.next_due = next_due;
+    }
+}
+
+fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(cmp::min(a, b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// `Drain` rate limiting records with a token bucket per call site
+///
+/// Every call site (`file`, `line` and `column` of a logging statement) gets
+/// its own token bucket, holding up to `burst` tokens, refilled at
+/// `per_second` tokens per second. Each passed `Record` takes one token;
+/// `Record`s arriving at an empty bucket are suppressed. This way one
+/// logging statement spamming in a hot loop is capped, while every other
+/// one is unaffected.
+///
+/// Use `RateLimit::by_tag` to share buckets between all the call sites
+/// using the same `Record::tag` instead.
+///
+/// When a bucket refills after suppressing some records, a summary
+/// `Record` (eg. `suppressed 4312 records from src/foo.rs:88`, or
+/// `suppressed 12 records tagged "db"` with `RateLimit::by_tag`) is passed
+/// to the wrapped drain, with the level, location and tag of the throttled
+/// call site, and a `suppressed` key carrying the count. There is no timer:
+/// the summary is passed with the next record logged through `RateLimit`
+/// (from any call site) after the bucket has a token again. Pending
+/// summaries are also emitted on `Drain::flush`, and when `RateLimit` is
+/// dropped.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = RateLimit::new(Discard, 10).burst(100);
+/// # let _ = drain;
+/// ```
+pub struct RateLimit<D: Drain> {
+    drain: D,
+    per_second: f64,
+    burst: f64,
+    by_tag: bool,
+    buckets: Mutex<Buckets>,
+}
+
+impl<D: Drain> RateLimit<D> {
+    /// Create `RateLimit` passing at most `per_second` records per second
+    /// from every call site to `drain`
+    pub fn new(drain: D, per_second: u32) -> Self {
+        RateLimit {
+            drain: drain,
+            per_second: f64::from(per_second),
+            burst: f64::from(per_second),
+            by_tag: false,
+            buckets: Mutex::new(Buckets {
+                by_location: HashMap::new(),
+                by_tag: HashMap::new(),
+                next_due: None,
+            }),
+        }
+    }
+
+    /// Set how many records can be passed in a burst
+    ///
+    /// Defaults to `per_second`.
+    pub fn burst(mut self, burst: u32) -> Self {
+        self.burst = f64::from(burst);
+        self
+    }
+
+    /// Key token buckets by `Record::tag` instead of call site
+    pub fn by_tag(mut self) -> Self {
+        self.by_tag = true;
+        self
+    }
+
+    /// Take a token for `record`
+    ///
+    /// Returns whether `record` should be passed, and summaries of
+    /// previously suppressed records (of this and other buckets) to pass
+    /// before it.
+    fn acquire(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> (bool, Vec<Summary>) {
+        let mut summaries = Vec::new();
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(_) => return (true, summaries),
+        };
+        let buckets = &mut *buckets;
+        let (per_second, burst) = (self.per_second, self.burst);
+
+        // a block, so `bucket` doesn't borrow `buckets` after it
+        let pass = {
+            let bucket = if self.by_tag {
+                let map = &mut buckets.by_tag;
+                if !map.contains_key(record.tag()) {
+                    map.insert(
+                        record.tag().to_string(),
+                        Bucket::new(record, burst),
+                    );
+                }
+                map.get_mut(record.tag()).unwrap()
+            } else {
+                let location = record.location();
+                buckets
+                    .by_location
+                    .entry((location.file, location.line, location.column))
+                    .or_insert_with(|| Bucket::new(record, burst))
+            };
+
+            bucket.refill(per_second, burst);
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                summaries.extend(bucket.take_summary(self.by_tag));
+                true
+            } else {
+                bucket.suppressed += 1;
+                bucket.level = record.level();
+                bucket.values = Some(logger_values.clone());
+                buckets.next_due =
+                    earliest(buckets.next_due, bucket.due(per_second));
+                false
+            }
+        };
+        buckets.take_due_summaries(
+            per_second,
+            burst,
+            self.by_tag,
+            &mut summaries,
+        );
+        (pass, summaries)
+    }
+
+    /// Pass summaries of all the call sites with suppressed records
+    fn pass_summaries(&self) {
+        let summaries: Vec<_> = match self.buckets.lock() {
+            Ok(mut buckets) => {
+                buckets.next_due = None;
+                let by_tag = self.by_tag;
+                buckets
+                    .all()
+                    .filter_map(|bucket| bucket.take_summary(by_tag))
+                    .collect()
+            }
+            Err(_) => return,
+        };
+        let empty = OwnedKVList::from(::OwnedKV(()));
+        for summary in summaries {
+            let _ = summary.log(&self.drain, &empty);
+        }
+    }
+}
+
+impl<D: Drain> Drain for RateLimit<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let (pass, summaries) = self.acquire(record, logger_values);
+        // All summaries and the record are passed even if a summary failed,
+        // and the first failure reported after them.
+        let mut summary_res = Ok(());
+        for summary in summaries {
+            let res = summary.log(&self.drain, logger_values);
+            summary_res = summary_res.and(res.map(|_| ()));
+        }
+        let ok = if pass {
+            Some(self.drain.log(record, logger_values)?)
+        } else {
+            None
+        };
+        summary_res.map(|_| ok)
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    /// Pass pending summaries, and flush the wrapped drain
+    fn flush(&self) -> Result {
+        self.pass_summaries();
+        self.drain.flush()
+    }
+    /// Pass pending summaries, and shut down the wrapped drain
+    fn shutdown(&self) -> Result {
+        self.pass_summaries();
+        self.drain.shutdown()
+    }
+}
+
+impl<D: Drain> Drop for RateLimit<D> {
+    fn drop(&mut self) {
+        self.pass_summaries();
+    }
+}
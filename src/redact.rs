@@ -0,0 +1,348 @@
+use core::fmt::{self, Write};
+use core::ops::Deref;
+use core::result;
+use std::string::{String, ToString};
+use std::sync::Arc;
+use std::vec::Vec;
+
+use key::key_str;
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {
+    BorrowedKV, Drain, Key, Level, OwnedKV, OwnedKVList, Record, RecordStatic,
+    Result, Serializer, WrappedValues, KV,
+};
+
+/// Replacement of values of redacted keys
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Redaction {
+    /// Replace the value with `"[REDACTED]"`
+    Mask,
+    /// Replace the value with a salted hash of its `Display` form
+    ///
+    /// The hash is 64-bit FNV-1a of the salt followed by the value, printed
+    /// as 16 hex digits. The algorithm is fixed, so equal values (logged
+    /// with the same salt) can be correlated across processes and versions,
+    /// but it's not meant to resist brute-forcing of values from small sets.
+    /// Nested `serde` values are masked instead.
+    Hash(String),
+    /// Drop the key-value pair completely
+    Remove,
+}
+
+/// Set of keys to redact, and how
+///
+/// Keys are compared ignoring ASCII case. Patterns can use `*` to match any
+/// (possibly empty) sequence of characters, eg. `*password*` or `auth_*`.
+///
+/// ```
+/// use slog::*;
+///
+/// let redactor = Redactor::new(Redaction::Mask)
+///     .key("email")
+///     .pattern("*password*");
+/// assert!(redactor.is_redacted("db_PASSWORD"));
+/// assert!(!redactor.is_redacted("user"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redactor {
+    redaction: Redaction,
+    keys: Vec<String>,
+    patterns: Vec<String>,
+}
+
+impl Redactor {
+    /// Create `Redactor` not redacting any keys yet
+    pub fn new(redaction: Redaction) -> Self {
+        Redactor {
+            redaction: redaction,
+            keys: vec![],
+            patterns: vec![],
+        }
+    }
+
+    /// Redact values of `key`
+    pub fn key(mut self, key: &str) -> Self {
+        self.keys.push(key.to_string());
+        self
+    }
+
+    /// Redact values of keys matching `pattern`
+    pub fn pattern(mut self, pattern: &str) -> Self {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    /// Returns `true` if no keys are redacted at all
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Returns `true` if values of `key` are redacted
+    pub fn is_redacted(&self, key: &str) -> bool {
+        self.keys.iter().any(|k| {
+            k.len() == key.len()
+                && k.bytes()
+                    .zip(key.bytes())
+                    .all(|(a, b)| eq_ignore_case(a, b))
+        }) || self
+            .patterns
+            .iter()
+            .any(|p| glob_matches(p.as_bytes(), key.as_bytes()))
+    }
+
+    /// Wrap `serializer` so values of redacted keys are replaced
+    pub fn serializer<'a, S: Serializer + ?Sized>(
+        &'a self,
+        serializer: &'a mut S,
+    ) -> RedactSerializer<'a, S> {
+        RedactSerializer {
+            serializer: serializer,
+            redactor: self,
+        }
+    }
+}
+
+/// Match `text` against `pattern` with `*` wildcards, ignoring ASCII case
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` in `pattern`, and of `text` it matched up to
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && eq_ignore_case(pattern[p], text[t]) {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // let the last `*` match one more character
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Compare bytes ignoring ASCII case, like `u8::eq_ignore_ascii_case`, which
+/// is not available on the oldest supported Rust
+fn eq_ignore_case(a: u8, b: u8) -> bool {
+    let lower = |c: u8| if b'A' <= c && c <= b'Z' { c + 32 } else { c };
+    lower(a) == lower(b)
+}
+
+/// 64-bit FNV-1a hash, fed through `fmt::Write`
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl fmt::Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        Ok(())
+    }
+}
+
+/// `Serializer` replacing values of keys redacted by a `Redactor`
+///
+/// Values of other keys are passed to the wrapped `Serializer` unchanged.
+/// Created with `Redactor::serializer`.
+pub struct RedactSerializer<'a, S: Serializer + ?Sized + 'a> {
+    serializer: &'a mut S,
+    redactor: &'a Redactor,
+}
+
+impl<'a, S: Serializer + ?Sized> RedactSerializer<'a, S> {
+    fn redact(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        match self.redactor.redaction {
+            Redaction::Mask => self.serializer.emit_str(key, "[REDACTED]"),
+            Redaction::Hash(ref salt) => {
+                let mut hasher = Fnv1a::new();
+                hasher.write_str(salt)?;
+                hasher.write_fmt(*val)?;
+                self.serializer
+                    .emit_arguments(key, &format_args!("{:016x}", hasher.0))
+            }
+            Redaction::Remove => Ok(()),
+        }
+    }
+}
+
+macro_rules! redact_emit(
+    ($t:ty, $f:ident) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            if self.redactor.is_redacted(key_str(&key)) {
+                self.redact(key, &format_args!("{}", val))
+            } else {
+                self.serializer.$f(key, val)
+            }
+        }
+    };
+);
+
+impl<'a, S: Serializer + ?Sized> Serializer for RedactSerializer<'a, S> {
+    redact_emit!(usize, emit_usize);
+    redact_emit!(isize, emit_isize);
+    redact_emit!(bool, emit_bool);
+    redact_emit!(char, emit_char);
+    redact_emit!(u8, emit_u8);
+    redact_emit!(i8, emit_i8);
+    redact_emit!(u16, emit_u16);
+    redact_emit!(i16, emit_i16);
+    redact_emit!(u32, emit_u32);
+    redact_emit!(i32, emit_i32);
+    redact_emit!(f32, emit_f32);
+    redact_emit!(u64, emit_u64);
+    redact_emit!(i64, emit_i64);
+    redact_emit!(f64, emit_f64);
+    redact_emit!(&str, emit_str);
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        if self.redactor.is_redacted(key_str(&key)) {
+            self.redact(key, &format_args!("()"))
+        } else {
+            self.serializer.emit_unit(key)
+        }
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        if self.redactor.is_redacted(key_str(&key)) {
+            self.redact(key, &format_args!(""))
+        } else {
+            self.serializer.emit_none(key)
+        }
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        if self.redactor.is_redacted(key_str(&key)) {
+            self.redact(key, val)
+        } else {
+            self.serializer.emit_arguments(key, val)
+        }
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        if !self.redactor.is_redacted(key_str(&key)) {
+            return self.serializer.emit_serde(key, value);
+        }
+        match self.redactor.redaction {
+            Redaction::Remove => Ok(()),
+            _ => self.serializer.emit_str(key, "[REDACTED]"),
+        }
+    }
+}
+
+/// `KV` serializing another `KV` through a `RedactSerializer`
+struct RedactedKV<K, R> {
+    kv: K,
+    redactor: R,
+}
+
+impl<K: KV, R: Deref<Target = Redactor>> KV for RedactedKV<K, R> {
+    fn serialize(
+        &self,
+        record: &Record,
+        serializer: &mut Serializer,
+    ) -> Result {
+        self.kv
+            .serialize(record, &mut self.redactor.serializer(serializer))
+    }
+}
+
+/// `Drain` redacting values of sensitive keys
+///
+/// Passes records to the wrapped drain with key-value pairs of both the
+/// `Record` and the `Logger` serialized through a `RedactSerializer`, so
+/// values of keys redacted by the `Redactor` never reach it.
+///
+/// The redacting wrapper of `Logger` values is built once and reused for as
+/// long as records keep coming from the same `Logger`; a `Redactor` with no
+/// keys or patterns passes everything through unchanged.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = Redact::new(
+///     Discard,
+///     Redactor::new(Redaction::Hash("s3cr3t".into()))
+///         .key("email")
+///         .pattern("*password*"),
+/// );
+/// # let _ = drain;
+/// ```
+pub struct Redact<D: Drain> {
+    drain: D,
+    redactor: Arc<Redactor>,
+    logger_values: WrappedValues,
+}
+
+impl<D: Drain> Redact<D> {
+    /// Create `Redact` wrapping `drain`
+    pub fn new(drain: D, redactor: Redactor) -> Self {
+        Redact {
+            drain: drain,
+            redactor: Arc::new(redactor),
+            logger_values: WrappedValues::new(),
+        }
+    }
+}
+
+impl<D: Drain> Drain for Redact<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        if self.redactor.is_empty() {
+            return self.drain.log(record, logger_values);
+        }
+        let rs = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+        let kv = RedactedKV {
+            kv: record.kv(),
+            redactor: &*self.redactor,
+        };
+        let logger_values = self.logger_values.get(logger_values, |values| {
+            OwnedKVList::from(OwnedKV(RedactedKV {
+                kv: values.clone(),
+                redactor: self.redactor.clone(),
+            }))
+        });
+        self.drain.log(
+            &Record::new(&rs, record.msg(), BorrowedKV(&kv)),
+            &logger_values,
+        )
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,351 @@
+use core::fmt;
+use core::ops::Deref;
+use core::result;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::panic::RefUnwindSafe;
+use std::string::{String, ToString};
+use std::sync::{Arc, RwLock};
+
+use key::{key_from_string, key_str};
+#[cfg(feature = "nested-values")]
+use SerdeValue;
+use {
+    BorrowedKV, Drain, Key, Level, OwnedKV, OwnedKVList, Record, RecordStatic,
+    Result, SendSyncRefUnwindSafeKV, Serializer, WrappedValues, KV,
+};
+
+// trait objects can't be `RefUnwindSafe` on the oldest supported Rust, so
+// `KeyRewriter` implements it, as `rename_with` only takes such functions
+type RenameFn = Fn(&str) -> Option<String> + Send + Sync;
+
+/// Rules for rewriting keys
+///
+/// A key is renamed using the mapping table (`KeyRewriter::rename`) first,
+/// then, if not in the table, using the function set with
+/// `KeyRewriter::rename_with`. Finally the prefix (`KeyRewriter::prefix`), if
+/// any, is prepended to every key.
+///
+/// Results are cached per key, so the renaming function must always return
+/// the same result for the same key. Rewritten keys are allocated once; with
+/// static keys (without `dynamic-keys` feature) they're leaked, as keys have
+/// to be `&'static str`, but only once per distinct key in the whole process,
+/// no matter how many `KeyRewriter`s produce it. Keys that are not rewritten
+/// are passed as they are, without allocating.
+///
+/// ```
+/// use slog::*;
+///
+/// let rewriter = KeyRewriter::new()
+///     .rename("method", "http.method")
+///     .rename_with(|key| if key.starts_with("db_") {
+///         Some(key.replacen("db_", "db.", 1))
+///     } else {
+///         None
+///     });
+/// assert_eq!(rewriter.rewrite("method"), Some("http.method".into()));
+/// assert_eq!(rewriter.rewrite("db_host"), Some("db.host".into()));
+/// assert!(rewriter.rewrite("user").is_none());
+/// ```
+pub struct KeyRewriter {
+    table: HashMap<String, String>,
+    rename_fn: Option<Box<RenameFn>>,
+    prefix: Option<String>,
+    cache: RwLock<HashMap<String, Option<Key>>>,
+}
+
+impl RefUnwindSafe for KeyRewriter {}
+
+impl KeyRewriter {
+    /// Create `KeyRewriter` not rewriting any keys
+    pub fn new() -> Self {
+        KeyRewriter {
+            table: HashMap::new(),
+            rename_fn: None,
+            prefix: None,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Rename key `from` to `to`
+    pub fn rename(mut self, from: &str, to: &str) -> Self {
+        self.table.insert(from.to_string(), to.to_string());
+        self
+    }
+
+    /// Rename keys not in the mapping table with `f`
+    ///
+    /// `f` returns `None` to leave the key as it is.
+    pub fn rename_with<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.rename_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Prepend `prefix` to every key
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Returns `true` if no keys are rewritten at all
+    fn is_empty(&self) -> bool {
+        self.table.is_empty()
+            && self.rename_fn.is_none()
+            && self.prefix.is_none()
+    }
+
+    fn rewrite_uncached(&self, key: &str) -> Option<String> {
+        let renamed = match self.table.get(key) {
+            Some(renamed) => Some(renamed.clone()),
+            None => self.rename_fn.as_ref().and_then(|f| f(key)),
+        };
+        match self.prefix {
+            Some(ref prefix) => {
+                let key = renamed.as_ref().map_or(key, |k| k.as_str());
+                Some(prefix.clone() + key)
+            }
+            None => renamed,
+        }
+    }
+
+    /// Get new name of `key`, or `None` if it's not rewritten
+    // `Key` is `Copy` only without `dynamic-keys`
+    #[cfg_attr(clippy, allow(renamed_and_removed_lints, clone_on_copy))]
+    pub fn rewrite(&self, key: &str) -> Option<Key> {
+        if let Ok(cache) = self.cache.read() {
+            if let Some(rewritten) = cache.get(key) {
+                return rewritten.clone();
+            }
+        }
+        let mut cache = match self.cache.write() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        // another thread might have been first
+        if let Some(rewritten) = cache.get(key) {
+            return rewritten.clone();
+        }
+        let rewritten = self.rewrite_uncached(key).map(key_from_string);
+        cache.insert(key.to_string(), rewritten.clone());
+        rewritten
+    }
+
+    /// Wrap `serializer` so keys are rewritten
+    pub fn serializer<'a, S: Serializer + ?Sized>(
+        &'a self,
+        serializer: &'a mut S,
+    ) -> RewriteSerializer<'a, S> {
+        RewriteSerializer {
+            serializer: serializer,
+            rewriter: self,
+        }
+    }
+}
+
+impl Default for KeyRewriter {
+    fn default() -> Self {
+        KeyRewriter::new()
+    }
+}
+
+/// `Serializer` rewriting keys with a `KeyRewriter`
+///
+/// Created with `KeyRewriter::serializer`.
+pub struct RewriteSerializer<'a, S: Serializer + ?Sized + 'a> {
+    serializer: &'a mut S,
+    rewriter: &'a KeyRewriter,
+}
+
+impl<'a, S: Serializer + ?Sized> RewriteSerializer<'a, S> {
+    fn key(&self, key: Key) -> Key {
+        match self.rewriter.rewrite(key_str(&key)) {
+            Some(rewritten) => rewritten,
+            None => key,
+        }
+    }
+}
+
+macro_rules! rewrite_emit(
+    ($t:ty, $f:ident) => {
+        fn $f(&mut self, key: Key, val: $t) -> Result {
+            let key = self.key(key);
+            self.serializer.$f(key, val)
+        }
+    };
+);
+
+impl<'a, S: Serializer + ?Sized> Serializer for RewriteSerializer<'a, S> {
+    rewrite_emit!(usize, emit_usize);
+    rewrite_emit!(isize, emit_isize);
+    rewrite_emit!(bool, emit_bool);
+    rewrite_emit!(char, emit_char);
+    rewrite_emit!(u8, emit_u8);
+    rewrite_emit!(i8, emit_i8);
+    rewrite_emit!(u16, emit_u16);
+    rewrite_emit!(i16, emit_i16);
+    rewrite_emit!(u32, emit_u32);
+    rewrite_emit!(i32, emit_i32);
+    rewrite_emit!(f32, emit_f32);
+    rewrite_emit!(u64, emit_u64);
+    rewrite_emit!(i64, emit_i64);
+    rewrite_emit!(f64, emit_f64);
+    rewrite_emit!(&str, emit_str);
+
+    fn emit_unit(&mut self, key: Key) -> Result {
+        let key = self.key(key);
+        self.serializer.emit_unit(key)
+    }
+
+    fn emit_none(&mut self, key: Key) -> Result {
+        let key = self.key(key);
+        self.serializer.emit_none(key)
+    }
+
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        let key = self.key(key);
+        self.serializer.emit_arguments(key, val)
+    }
+
+    #[cfg(feature = "nested-values")]
+    fn emit_serde(&mut self, key: Key, value: &SerdeValue) -> Result {
+        let key = self.key(key);
+        self.serializer.emit_serde(key, value)
+    }
+}
+
+/// `KV` serializing another `KV` through a `RewriteSerializer`
+///
+/// Use `Prefixed::new` to prefix keys of a child `Logger`.
+pub struct Prefixed<K, R = Arc<KeyRewriter>> {
+    kv: K,
+    rewriter: R,
+}
+
+impl<K: SendSyncRefUnwindSafeKV> Prefixed<K> {
+    /// Prefix all the keys of `values`, eg. for `Logger::new`
+    ///
+    /// Keys of records logged through the child `Logger` are not prefixed;
+    /// wrap its drain with `RewriteKeys` for that.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate slog;
+    ///
+    /// use slog::*;
+    ///
+    /// fn main() {
+    ///     let log = Logger::root(Discard, o!());
+    ///     let db = log.new(Prefixed::new("db.", o!("host" => "db1")));
+    ///     # let _ = db;
+    /// }
+    /// ```
+    pub fn new(prefix: &str, values: OwnedKV<K>) -> OwnedKV<Self> {
+        OwnedKV(Prefixed {
+            kv: values.0,
+            rewriter: Arc::new(KeyRewriter::new().prefix(prefix)),
+        })
+    }
+}
+
+impl<K: KV, R: Deref<Target = KeyRewriter>> KV for Prefixed<K, R> {
+    fn serialize(
+        &self,
+        record: &Record,
+        serializer: &mut Serializer,
+    ) -> Result {
+        self.kv
+            .serialize(record, &mut self.rewriter.serializer(serializer))
+    }
+}
+
+/// `Drain` rewriting keys with a `KeyRewriter`
+///
+/// Keys of both the `Record` and the `Logger` are rewritten; the rewriting
+/// wrapper of `Logger` values is built once and reused for as long as
+/// records keep coming from the same `Logger`, and a `KeyRewriter` with no
+/// rules passes everything through unchanged. Wrapping a
+/// `Logger` and using it as a drain of a new root `Logger` rewrites only the
+/// keys added in the new one:
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::*;
+///
+/// fn main() {
+///     let log = Logger::root(Discard, o!("app" => "x"));
+///     let rewriter = KeyRewriter::new().prefix("db.");
+///     let db = Logger::root(RewriteKeys::new(log.clone(), rewriter), o!());
+///     // logs `db.host`, and `app`
+///     info!(db, "connected"; "host" => "db1");
+/// }
+/// ```
+pub struct RewriteKeys<D: Drain> {
+    drain: D,
+    rewriter: Arc<KeyRewriter>,
+    logger_values: WrappedValues,
+}
+
+impl<D: Drain> RewriteKeys<D> {
+    /// Create `RewriteKeys` wrapping `drain`
+    pub fn new(drain: D, rewriter: KeyRewriter) -> Self {
+        RewriteKeys {
+            drain: drain,
+            rewriter: Arc::new(rewriter),
+            logger_values: WrappedValues::new(),
+        }
+    }
+}
+
+impl<D: Drain> Drain for RewriteKeys<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        if self.rewriter.is_empty() {
+            return self.drain.log(record, logger_values);
+        }
+        let rs = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+        let kv = Prefixed {
+            kv: record.kv(),
+            rewriter: &*self.rewriter,
+        };
+        let logger_values = self.logger_values.get(logger_values, |values| {
+            OwnedKVList::from(OwnedKV(Prefixed {
+                kv: values.clone(),
+                rewriter: self.rewriter.clone(),
+            }))
+        });
+        self.drain.log(
+            &Record::new(&rs, record.msg(), BorrowedKV(&kv)),
+            &logger_values,
+        )
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
@@ -0,0 +1,188 @@
+use core::{iter, option, result, slice};
+use std::string::{String, ToString};
+use std::sync::Arc;
+use std::vec::Vec;
+
+use {
+    Drain, Level, Never, OwnedKVList, Record, Result,
+    SendSyncRefUnwindSafeDrain,
+};
+
+type ErasedDrain<O, E> = Arc<SendSyncRefUnwindSafeDrain<Ok = O, Err = E>>;
+/// Tag or prefix, and its drain
+type Route<O, E> = (String, ErasedDrain<O, E>);
+/// Iterator over drains of all the routes, returned by `Router::routes`
+type Routes<'a, O, E> = iter::Chain<
+    iter::Map<
+        iter::Chain<slice::Iter<'a, Route<O, E>>, slice::Iter<'a, Route<O, E>>>,
+        fn(&'a Route<O, E>) -> &'a ErasedDrain<O, E>,
+    >,
+    option::Iter<'a, ErasedDrain<O, E>>,
+>;
+
+/// `Drain` passing records to different drains, by `Record::tag`
+///
+/// A record goes to exactly one route:
+///
+/// * the drain registered with `Router::exact` for its tag, if any,
+/// * otherwise the drain registered with `Router::prefix` for the longest
+///   prefix of its tag, if any,
+/// * otherwise the `Router::default` drain, if set.
+///
+/// Records not matching any route are dropped. `is_enabled` returns `true`
+/// if any of the routes is enabled at given level.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::*;
+///
+/// fn main() {
+///     let drain = Router::new()
+///         .exact("audit", Discard)
+///         .prefix("perf", Discard)
+///         .default(Discard);
+///
+///     let log = Logger::root(drain.fuse(), o!());
+///     info!(log, #"audit", "user logged in"; "user" => "alice");
+///     info!(log, #"perf::db", "query"; "ms" => 12);
+///     info!(log, "goes to the default route");
+/// }
+/// ```
+pub struct Router<O = (), E = Never> {
+    exact: Vec<Route<O, E>>,
+    prefix: Vec<Route<O, E>>,
+    default: Option<ErasedDrain<O, E>>,
+}
+
+impl<O, E> Clone for Router<O, E> {
+    fn clone(&self) -> Self {
+        Router {
+            exact: self.exact.clone(),
+            prefix: self.prefix.clone(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+impl<O, E> Router<O, E> {
+    /// Create `Router` without any routes
+    pub fn new() -> Self {
+        Router {
+            exact: vec![],
+            prefix: vec![],
+            default: None,
+        }
+    }
+
+    /// Route records tagged exactly `tag` to `drain`
+    ///
+    /// Replaces previous route for the same `tag`.
+    pub fn exact<D>(mut self, tag: &str, drain: D) -> Self
+    where
+        D: SendSyncRefUnwindSafeDrain<Ok = O, Err = E> + 'static,
+    {
+        self.exact.retain(|&(ref t, _)| t != tag);
+        self.exact.push((tag.to_string(), Arc::new(drain)));
+        self
+    }
+
+    /// Route records with tags starting with `prefix` to `drain`
+    ///
+    /// Replaces previous route for the same `prefix`.
+    pub fn prefix<D>(mut self, prefix: &str, drain: D) -> Self
+    where
+        D: SendSyncRefUnwindSafeDrain<Ok = O, Err = E> + 'static,
+    {
+        self.prefix.retain(|&(ref p, _)| p != prefix);
+        self.prefix.push((prefix.to_string(), Arc::new(drain)));
+        // longest prefixes first, so the first match is the best one
+        self.prefix.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        self
+    }
+
+    /// Route records not matching any other route to `drain`
+    pub fn default<D>(mut self, drain: D) -> Self
+    where
+        D: SendSyncRefUnwindSafeDrain<Ok = O, Err = E> + 'static,
+    {
+        self.default = Some(Arc::new(drain));
+        self
+    }
+
+    fn route(&self, tag: &str) -> Option<&ErasedDrain<O, E>> {
+        self.exact
+            .iter()
+            .find(|&&(ref t, _)| t == tag)
+            .or_else(|| {
+                self.prefix
+                    .iter()
+                    .find(|&&(ref p, _)| tag.starts_with(&**p))
+            })
+            .map(|&(_, ref drain)| drain)
+            .or(self.default.as_ref())
+    }
+
+    fn routes<'a>(&'a self) -> Routes<'a, O, E> {
+        // lifetimes can't be elided here on the oldest supported Rust
+        #[cfg_attr(
+            clippy,
+            allow(renamed_and_removed_lints, needless_lifetimes)
+        )]
+        fn drain<'a, O, E>(route: &'a Route<O, E>) -> &'a ErasedDrain<O, E> {
+            &route.1
+        }
+
+        self.exact
+            .iter()
+            .chain(self.prefix.iter())
+            .map(drain as fn(&'a Route<O, E>) -> &'a ErasedDrain<O, E>)
+            .chain(self.default.iter())
+    }
+}
+
+impl<O, E> Default for Router<O, E> {
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl<O, E> Drain for Router<O, E> {
+    type Ok = Option<O>;
+    type Err = E;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        match self.route(record.tag()) {
+            Some(drain) => drain.log(record, logger_values).map(Some),
+            None => Ok(None),
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.routes().any(|drain| drain.is_enabled(level))
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.routes().all(|drain| drain.is_interest_static())
+    }
+    /// Flush all the routes, returning the first error
+    fn flush(&self) -> Result {
+        let mut res = Ok(());
+        for drain in self.routes() {
+            res = res.and(drain.flush());
+        }
+        res
+    }
+    /// Shut down all the routes, returning the first error
+    fn shutdown(&self) -> Result {
+        let mut res = Ok(());
+        for drain in self.routes() {
+            res = res.and(drain.shutdown());
+        }
+        res
+    }
+}
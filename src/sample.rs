@@ -0,0 +1,148 @@
+use core::result;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use {Drain, Level, OwnedKVList, Record, Result};
+
+/// `Drain` passing through only a fraction of logging records
+///
+/// Every logging `Level` has its own sampling rate - a probability between
+/// `0.0` (drop everything) and `1.0` (keep everything) that a `Record` of
+/// that level will be passed to the wrapped drain. This allows keeping a
+/// statistically representative trickle of high-volume output (eg.
+/// `Debug` and `Trace`) while keeping all the important records: `Critical`
+/// and `Error` records are always kept, unless their rate is lowered with
+/// `Sample::level_rate`.
+///
+/// Random decisions are made using a small, lock-free pseudo-random number
+/// generator. Use `Sample::seed` to make them deterministic (eg. in tests).
+///
+/// See `Drain::sample` for convenience function.
+///
+/// ```
+/// use slog::*;
+///
+/// let drain = Discard
+///     .sample(0.01)
+///     .level_rate(Level::Warning, 1.0)
+///     .level_rate(Level::Info, 0.5);
+/// # let _ = drain;
+/// ```
+pub struct Sample<D: Drain> {
+    drain: D,
+    rates: [f64; 6],
+    seed: u64,
+    counter: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl<D: Drain> Sample<D> {
+    /// Create `Sample` wrapping given `drain`, keeping all `Critical` and
+    /// `Error` records, and `rate` fraction of records of other levels
+    pub fn new(drain: D, rate: f64) -> Self {
+        Sample {
+            drain: drain,
+            rates: [1.0, 1.0, rate, rate, rate, rate],
+            seed: default_seed(),
+            counter: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Set sampling rate for records of a given `level`
+    pub fn level_rate(mut self, level: Level, rate: f64) -> Self {
+        self.rates[level.as_usize() - 1] = rate;
+        self
+    }
+
+    /// Use a fixed `seed` for sampling decisions
+    ///
+    /// Two `Sample` drains with the same seed and rates will make exactly
+    /// the same decisions for the same sequence of records.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.counter = AtomicUsize::new(0);
+        self
+    }
+
+    /// Number of records dropped so far
+    ///
+    /// Wrap `Sample` in an `Arc` to be able to read it after passing the
+    /// drain to a `Logger`.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn rate(&self, level: Level) -> f64 {
+        self.rates[level.as_usize() - 1]
+    }
+
+    /// Splitmix64 over an atomic counter
+    fn next_f64(&self) -> f64 {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed) as u64;
+        let mut z = self.seed.wrapping_add(
+            n.wrapping_add(1).wrapping_mul(0x9e37_79b9_7f4a_7c15),
+        );
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn keep(&self, level: Level) -> bool {
+        let rate = self.rate(level);
+        if rate >= 1.0 {
+            true
+        } else if rate <= 0.0 {
+            false
+        } else {
+            self.next_f64() < rate
+        }
+    }
+}
+
+impl<D: Drain> Drain for Sample<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        if self.keep(record.level()) {
+            Ok(Some(self.drain.log(record, logger_values)?))
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            Ok(None)
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.rate(level) > 0.0 && self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
+
+#[cfg(feature = "std")]
+fn default_seed() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() ^ (u64::from(d.subsec_nanos()) << 32))
+        .unwrap_or(0)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_seed() -> u64 {
+    0x2545_f491_4f6c_dd1d
+}
@@ -0,0 +1,180 @@
+use core::result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use {Drain, Level, OwnedKVList, Record, Result};
+
+/// Counters shared by `Stats` and its `StatsHandle`s
+struct Counters {
+    /// Records by `Level::as_usize() - 1`
+    records: [AtomicUsize; 6],
+    errors: AtomicUsize,
+    rejected: AtomicUsize,
+}
+
+/// Values of `Stats` counters at some point in time
+///
+/// Counters only grow (wrapping around on overflow), so rates can be
+/// computed from two snapshots with `StatsSnapshot::since`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsSnapshot {
+    records: [usize; 6],
+    errors: usize,
+    rejected: usize,
+}
+
+impl StatsSnapshot {
+    /// Number of records at `level`
+    pub fn records(&self, level: Level) -> usize {
+        self.records[level.as_usize() - 1]
+    }
+
+    /// Number of records at all levels
+    pub fn total(&self) -> usize {
+        self.records
+            .iter()
+            .fold(0, |total, &records| total.wrapping_add(records))
+    }
+
+    /// Number of errors returned by the wrapped drain
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Number of records that reached `Stats`, but the wrapped drain was
+    /// not enabled for
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// Counts since an `earlier` snapshot
+    pub fn since(&self, earlier: &StatsSnapshot) -> StatsSnapshot {
+        let mut records = [0; 6];
+        for (i, records) in records.iter_mut().enumerate() {
+            *records = self.records[i].wrapping_sub(earlier.records[i]);
+        }
+        StatsSnapshot {
+            records: records,
+            errors: self.errors.wrapping_sub(earlier.errors),
+            rejected: self.rejected.wrapping_sub(earlier.rejected),
+        }
+    }
+}
+
+/// Handle reading counters of a `Stats`
+///
+/// It can be cloned and sent between threads freely.
+#[derive(Clone)]
+pub struct StatsHandle(Arc<Counters>);
+
+impl StatsHandle {
+    /// Read current values of the counters
+    ///
+    /// Counters are read one by one, without stopping logging threads, so
+    /// a snapshot taken while logging is not guaranteed to be consistent
+    /// across counters.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        let mut records = [0; 6];
+        for (i, records) in records.iter_mut().enumerate() {
+            *records = self.0.records[i].load(Ordering::Relaxed);
+        }
+        StatsSnapshot {
+            records: records,
+            errors: self.0.errors.load(Ordering::Relaxed),
+            rejected: self.0.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `Drain` counting records passing through it
+///
+/// Counts records by `Level`, errors returned by the wrapped drain, and
+/// records rejected because the wrapped drain is not enabled for their
+/// level (`Drain::is_enabled`), which are then not passed to it. Counters
+/// are atomic, without any locking, and can be read through a
+/// `StatsHandle`.
+///
+/// `Stats::is_enabled` asks the wrapped drain, so logging macros skip
+/// records of disabled levels before they reach `Stats`: such records are
+/// not counted at all. Only records that reach `Stats` anyway (eg. when it
+/// is a branch of `Duplicate`) are counted as rejected.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::logfmt::Logfmt;
+/// use slog::*;
+///
+/// fn main() {
+///     let drain = Stats::new(Logfmt::new(std::io::sink()));
+///     let stats = drain.handle();
+///     let log = Logger::root(drain.ignore_res(), o!());
+///     error!(log, "timeout");
+///     assert_eq!(stats.snapshot().records(Level::Error), 1);
+/// }
+/// ```
+pub struct Stats<D: Drain> {
+    drain: D,
+    counters: Arc<Counters>,
+}
+
+impl<D: Drain> Stats<D> {
+    /// Create `Stats` wrapping `drain`, with all counters at zero
+    pub fn new(drain: D) -> Self {
+        Stats {
+            drain: drain,
+            counters: Arc::new(Counters {
+                records: Default::default(),
+                errors: AtomicUsize::new(0),
+                rejected: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Get a handle to read counters of this `Stats`
+    pub fn handle(&self) -> StatsHandle {
+        StatsHandle(self.counters.clone())
+    }
+}
+
+impl<D: Drain> Drain for Stats<D> {
+    type Ok = Option<D::Ok>;
+    type Err = D::Err;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let level = record.level();
+        self.counters.records[level.as_usize() - 1]
+            .fetch_add(1, Ordering::Relaxed);
+        if !self.drain.is_enabled(level) {
+            self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+        match self.drain.log(record, logger_values) {
+            Ok(ok) => Ok(Some(ok)),
+            Err(e) => {
+                self.counters.errors.fetch_add(1, Ordering::Relaxed);
+                Err(e)
+            }
+        }
+    }
+    #[inline]
+    fn is_enabled(&self, level: Level) -> bool {
+        self.drain.is_enabled(level)
+    }
+    #[inline]
+    fn is_interest_static(&self) -> bool {
+        self.drain.is_interest_static()
+    }
+    #[inline]
+    fn flush(&self) -> Result {
+        self.drain.flush()
+    }
+    #[inline]
+    fn shutdown(&self) -> Result {
+        self.drain.shutdown()
+    }
+}
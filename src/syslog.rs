@@ -0,0 +1,373 @@
+//! Syslog output, in RFC 5424 format
+//!
+//! `Syslog` is a `Drain` sending every record as one RFC 5424 message:
+//!
+//! ```text
+//! <14>1 2018-03-28T12:34:56.789012Z web1 server 4321 - [slog@32473 port="8080"] started
+//! ```
+//!
+//! Key-value pairs of the record and its `Logger` are placed in one
+//! STRUCTURED-DATA element, with values in their `Display` form. Messages
+//! can be sent over a Unix datagram socket (eg. `/dev/log`), UDP, or TCP
+//! with octet-counting framing (RFC 6587).
+
+use core::fmt::{self, Write as FmtWrite};
+use core::result;
+use std::env;
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::Path;
+use std::string::{String, ToString};
+use std::sync::{Mutex, MutexGuard};
+use std::time::SystemTime;
+use std::vec::Vec;
+
+use enrich::{find_hostname, process_id, Rfc3339};
+use key::key_str;
+use {Drain, Error, Key, Level, OwnedKVList, Record, Result, Serializer, KV};
+
+/// Syslog facility
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+    /// Kernel messages
+    Kern = 0,
+    /// User-level messages
+    User = 1,
+    /// Mail system
+    Mail = 2,
+    /// System daemons
+    Daemon = 3,
+    /// Security/authorization messages
+    Auth = 4,
+    /// Messages generated internally by syslogd
+    Syslog = 5,
+    /// Line printer subsystem
+    Lpr = 6,
+    /// Network news subsystem
+    News = 7,
+    /// UUCP subsystem
+    Uucp = 8,
+    /// Clock daemon
+    Cron = 9,
+    /// Security/authorization messages (private)
+    Authpriv = 10,
+    /// FTP daemon
+    Ftp = 11,
+    /// Local use 0
+    Local0 = 16,
+    /// Local use 1
+    Local1 = 17,
+    /// Local use 2
+    Local2 = 18,
+    /// Local use 3
+    Local3 = 19,
+    /// Local use 4
+    Local4 = 20,
+    /// Local use 5
+    Local5 = 21,
+    /// Local use 6
+    Local6 = 22,
+    /// Local use 7
+    Local7 = 23,
+}
+
+/// Syslog severity of `level`
+///
+/// `Critical` is 2 (critical), `Error` 3 (error), `Warning` 4 (warning),
+/// `Info` 6 (informational), and `Debug` and `Trace` are 7 (debug).
+pub fn severity(level: Level) -> u8 {
+    match level {
+        Level::Critical => 2,
+        Level::Error => 3,
+        Level::Warning => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Write header field `s`: `-` if empty, with characters outside of
+/// printable US-ASCII replaced with `_`, and cut to `max_len` characters
+fn write_header_field(out: &mut String, s: &str, max_len: usize) {
+    if s.is_empty() {
+        out.push('-');
+        return;
+    }
+    for c in s.chars().take(max_len) {
+        out.push(if c > ' ' && c <= '~' { c } else { '_' });
+    }
+}
+
+/// Write SD-NAME `s` (SD-ID or PARAM-NAME): like `write_header_field`, and
+/// with `=`, `]` and `"` replaced with `_` too
+fn write_sd_name(out: &mut String, s: &str) {
+    if s.is_empty() {
+        out.push('_');
+        return;
+    }
+    for c in s.chars().take(32) {
+        out.push(match c {
+            '=' | ']' | '"' => '_',
+            c if c > ' ' && c <= '~' => c,
+            _ => '_',
+        });
+    }
+}
+
+/// `fmt::Write` escaping `"`, `\` and `]` for PARAM-VALUE
+struct ParamValue<'a>(&'a mut String);
+
+impl<'a> fmt::Write for ParamValue<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if c == '"' || c == '\\' || c == ']' {
+                self.0.push('\\');
+            }
+            self.0.push(c);
+        }
+        Ok(())
+    }
+}
+
+/// `Serializer` writing SD-PARAMs of a STRUCTURED-DATA element
+struct SdSerializer<'a>(&'a mut String);
+
+impl<'a> Serializer for SdSerializer<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> Result {
+        self.0.push(' ');
+        write_sd_name(self.0, key_str(&key));
+        self.0.push_str("=\"");
+        ParamValue(self.0).write_fmt(*val)?;
+        self.0.push('"');
+        Ok(())
+    }
+}
+
+/// Bind an UDP socket, and connect it to `server`
+#[doc(hidden)]
+pub fn connect_udp<A: ToSocketAddrs>(server: A) -> io::Result<UdpSocket> {
+    let server = server.to_socket_addrs()?.next().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "no address")
+    })?;
+    let local = match server {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+    Ok(socket)
+}
+
+/// Socket `Syslog` sends messages with
+enum Transport {
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+    /// Stream is `None` after an error, until reconnected
+    Tcp(Vec<SocketAddr>, Option<TcpStream>),
+}
+
+impl Transport {
+    fn send(&mut self, msg: &[u8]) -> io::Result<()> {
+        match *self {
+            #[cfg(unix)]
+            Transport::Unix(ref socket) => socket.send(msg).map(|_| ()),
+            Transport::Udp(ref socket) => socket.send(msg).map(|_| ()),
+            Transport::Tcp(ref addrs, ref mut stream) => {
+                // octet-counting framing, RFC 6587
+                let mut frame = Vec::with_capacity(msg.len() + 8);
+                write!(frame, "{} ", msg.len())?;
+                frame.extend_from_slice(msg);
+                let mut connected = match stream.take() {
+                    Some(connected) => connected,
+                    None => TcpStream::connect(&addrs[..])?,
+                };
+                connected.write_all(&frame)?;
+                *stream = Some(connected);
+                Ok(())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Transport::Tcp(_, Some(ref mut stream)) => stream.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `Drain` sending records to syslog, in RFC 5424 format
+///
+/// `Level` is mapped to severity with `severity`. The facility is `User` by
+/// default, the app-name is the name of the executable, and the hostname is
+/// found like by `Hostname` provider. PROCID is the id of the process, and
+/// MSGID the tag of the record (or `-`).
+///
+/// Key-value pairs go to a STRUCTURED-DATA element with SD-ID `slog@32473`
+/// by default (32473 is the private enterprise number reserved for
+/// documentation, so it's better to set your own with `Syslog::sd_id`).
+///
+/// Messages are sent while holding a lock of the socket. If sending over
+/// TCP fails, the connection is reestablished for the next record.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::syslog::{Facility, Syslog};
+/// use slog::*;
+///
+/// fn main() {
+///     # let listener = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+///     # let addr = listener.local_addr().unwrap();
+///     let drain = Syslog::udp(addr)
+///         .unwrap()
+///         .facility(Facility::Local0)
+///         .app_name("server");
+///     let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+///     info!(log, "started"; "port" => 8080);
+/// }
+/// ```
+pub struct Syslog {
+    transport: Mutex<Transport>,
+    facility: Facility,
+    hostname: Option<String>,
+    app_name: String,
+    sd_id: String,
+}
+
+impl Syslog {
+    fn new(transport: Transport) -> Self {
+        let app_name = env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .unwrap_or_default();
+        Syslog {
+            transport: Mutex::new(transport),
+            facility: Facility::User,
+            hostname: find_hostname(),
+            app_name: app_name,
+            sd_id: "slog@32473".to_string(),
+        }
+    }
+
+    /// Create `Syslog` sending to a Unix datagram socket at `path`, like
+    /// `/dev/log`
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(Syslog::new(Transport::Unix(socket)))
+    }
+
+    /// Create `Syslog` sending to `server` over UDP
+    pub fn udp<A: ToSocketAddrs>(server: A) -> io::Result<Self> {
+        Ok(Syslog::new(Transport::Udp(connect_udp(server)?)))
+    }
+
+    /// Create `Syslog` sending to `server` over TCP, connecting right away
+    pub fn tcp<A: ToSocketAddrs>(server: A) -> io::Result<Self> {
+        let addrs: Vec<SocketAddr> = server.to_socket_addrs()?.collect();
+        let stream = TcpStream::connect(&addrs[..])?;
+        Ok(Syslog::new(Transport::Tcp(addrs, Some(stream))))
+    }
+
+    /// Set facility
+    pub fn facility(mut self, facility: Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// Set app-name (cut to 48 characters)
+    pub fn app_name(mut self, app_name: &str) -> Self {
+        self.app_name = app_name.to_string();
+        self
+    }
+
+    /// Set hostname, or `None` to send `-`
+    pub fn hostname(mut self, hostname: Option<String>) -> Self {
+        self.hostname = hostname;
+        self
+    }
+
+    /// Set SD-ID of the STRUCTURED-DATA element with key-value pairs
+    pub fn sd_id(mut self, sd_id: &str) -> Self {
+        self.sd_id = sd_id.to_string();
+        self
+    }
+
+    /// Format `record` as RFC 5424 message (without transport framing)
+    pub fn format(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> Result<String> {
+        let mut msg = String::with_capacity(256);
+        write!(
+            msg,
+            "<{}>1 {} ",
+            self.facility as u8 * 8 + severity(record.level()),
+            Rfc3339(SystemTime::now())
+        )?;
+        write_header_field(
+            &mut msg,
+            self.hostname.as_ref().map_or("", |h| h.as_str()),
+            255,
+        );
+        msg.push(' ');
+        write_header_field(&mut msg, &self.app_name, 48);
+        write!(msg, " {} ", process_id())?;
+        write_header_field(&mut msg, record.tag(), 32);
+        msg.push(' ');
+        let sd_start = msg.len();
+        msg.push('[');
+        write_sd_name(&mut msg, &self.sd_id);
+        let params_start = msg.len();
+        record.kv().serialize(record, &mut SdSerializer(&mut msg))?;
+        logger_values.serialize(record, &mut SdSerializer(&mut msg))?;
+        if msg.len() == params_start {
+            // no SD-PARAMs, so no STRUCTURED-DATA at all
+            msg.truncate(sd_start);
+            msg.push('-');
+        } else {
+            msg.push(']');
+        }
+        write!(msg, " {}", record.msg())?;
+        Ok(msg)
+    }
+
+    fn lock(&self) -> MutexGuard<Transport> {
+        match self.transport.lock() {
+            Ok(transport) => transport,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Drain for Syslog {
+    type Ok = ();
+    type Err = Error;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let msg = self.format(record, logger_values)?;
+        Ok(self.lock().send(msg.as_bytes())?)
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+    fn flush(&self) -> Result {
+        Ok(self.lock().flush()?)
+    }
+    fn shutdown(&self) -> Result {
+        self.flush()
+    }
+}
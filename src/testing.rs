@@ -0,0 +1,321 @@
+//! Utilities for testing logging behaviour
+//!
+//! `Capture` is a `Drain` keeping owned copies of all the records logged
+//! through it, which can be then queried, or checked with `assert_logged!`:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate slog;
+//!
+//! use slog::testing::Capture;
+//! use slog::*;
+//!
+//! fn main() {
+//!     let capture = Capture::new();
+//!     let log = Logger::root(capture.clone().fuse(), o!("peer" => "db1"));
+//!     error!(log, "connection timeout"; "attempt" => 3);
+//!
+//!     assert_logged!(capture, Level::Error, msg ~= "timeout", "peer" => "db1");
+//!     assert_logged!(capture, Level::Error, "attempt" => 3);
+//!     assert_eq!(capture.records_at(Level::Warning).len(), 0);
+//!     assert_eq!(capture.find_by_key("attempt").len(), 1);
+//! }
+//! ```
+
+use core::{fmt, result};
+use std::string::{String, ToString};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::vec::Vec;
+
+use key::key_str;
+use owned::serialize_kv;
+use {
+    Drain, Error, Key, Level, OwnedKVList, OwnedValue, Record, RecordLocation,
+};
+
+/// Record captured by `Capture`
+///
+/// Key-value pairs of both the record and its `Logger` are serialized into
+/// `OwnedValue`s when the record is captured.
+#[derive(Clone)]
+pub struct CapturedRecord {
+    location: RecordLocation,
+    tag: String,
+    level: Level,
+    msg: String,
+    kv: Vec<(Key, OwnedValue)>,
+    logger_kv: Vec<(Key, OwnedValue)>,
+}
+
+impl CapturedRecord {
+    /// Get record logging level
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Get formatted message
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    /// Get tag
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Get file of the logging statement
+    pub fn file(&self) -> &'static str {
+        self.location.file
+    }
+
+    /// Get line of the logging statement
+    pub fn line(&self) -> u32 {
+        self.location.line
+    }
+
+    /// Get module of the logging statement
+    pub fn module(&self) -> &'static str {
+        self.location.module
+    }
+
+    /// Get record key-value pairs, in order they were emitted in
+    pub fn kv(&self) -> &[(Key, OwnedValue)] {
+        &self.kv
+    }
+
+    /// Get key-value pairs of the `Logger` (children before parents)
+    pub fn logger_kv(&self) -> &[(Key, OwnedValue)] {
+        &self.logger_kv
+    }
+
+    /// Get value of `key`, looked up in record key-value pairs first, and
+    /// then in the ones of the `Logger`
+    pub fn get(&self, key: &str) -> Option<&OwnedValue> {
+        self.kv
+            .iter()
+            .chain(self.logger_kv.iter())
+            .find(|&&(ref k, _)| key_str(k) == key)
+            .map(|&(_, ref v)| v)
+    }
+}
+
+impl fmt::Debug for CapturedRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CapturedRecord")
+            .field("level", &self.level)
+            .field("msg", &self.msg)
+            .field("tag", &self.tag)
+            .field("file", &self.location.file)
+            .field("line", &self.location.line)
+            .field("kv", &self.kv)
+            .field("logger_kv", &self.logger_kv)
+            .finish()
+    }
+}
+
+/// Description of a record, for `Capture::matching`
+///
+/// Usually created by `assert_logged!`. Values are compared in their
+/// `Display` form, so `"attempt" => 3` matches both `3` and `"3"`.
+#[derive(Debug, Clone)]
+pub struct Expectation {
+    level: Level,
+    msg: Option<String>,
+    msg_contains: Option<String>,
+    kv: Vec<(String, String)>,
+}
+
+impl Expectation {
+    /// Expect a record at `level`
+    pub fn new(level: Level) -> Self {
+        Expectation {
+            level: level,
+            msg: None,
+            msg_contains: None,
+            kv: vec![],
+        }
+    }
+
+    /// Expect given message
+    pub fn msg(mut self, msg: &str) -> Self {
+        self.msg = Some(msg.to_string());
+        self
+    }
+
+    /// Expect message containing `part`
+    pub fn msg_contains(mut self, part: &str) -> Self {
+        self.msg_contains = Some(part.to_string());
+        self
+    }
+
+    /// Expect `key` with `value` (in record or `Logger` key-value pairs)
+    pub fn kv<V: fmt::Display>(mut self, key: &str, value: V) -> Self {
+        self.kv.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Returns `true` if `record` matches
+    pub fn matches(&self, record: &CapturedRecord) -> bool {
+        record.level == self.level
+            && self.msg.as_ref().map(|m| *m == record.msg).unwrap_or(true)
+            && self
+                .msg_contains
+                .as_ref()
+                .map(|m| record.msg.contains(m.as_str()))
+                .unwrap_or(true)
+            && self.kv.iter().all(|&(ref key, ref value)| {
+                record
+                    .get(key)
+                    .map(|v| v.to_string() == *value)
+                    .unwrap_or(false)
+            })
+    }
+}
+
+/// `Drain` capturing records, for tests
+///
+/// Clones share captured records, so keep one and pass another to the
+/// `Logger`. Fails only if serializing key-value pairs fails.
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Vec<CapturedRecord>>>);
+
+impl Capture {
+    /// Create `Capture` without any records
+    pub fn new() -> Self {
+        Capture::default()
+    }
+
+    fn lock(&self) -> MutexGuard<Vec<CapturedRecord>> {
+        // a panicking test must not hide records from other threads
+        match self.0.lock() {
+            Ok(records) => records,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Get all the captured records, oldest first
+    pub fn records(&self) -> Vec<CapturedRecord> {
+        self.lock().clone()
+    }
+
+    /// Get records at `level`
+    pub fn records_at(&self, level: Level) -> Vec<CapturedRecord> {
+        self.filter(|record| record.level == level)
+    }
+
+    /// Get records with `key` (in record or `Logger` key-value pairs)
+    pub fn find_by_key(&self, key: &str) -> Vec<CapturedRecord> {
+        self.filter(|record| record.get(key).is_some())
+    }
+
+    /// Get records matching `expectation`
+    pub fn matching(&self, expectation: &Expectation) -> Vec<CapturedRecord> {
+        self.filter(|record| expectation.matches(record))
+    }
+
+    /// Get records for which `f` returns `true`
+    pub fn filter<F>(&self, mut f: F) -> Vec<CapturedRecord>
+    where
+        F: FnMut(&CapturedRecord) -> bool,
+    {
+        self.lock().iter().filter(|r| f(r)).cloned().collect()
+    }
+
+    /// Number of captured records
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no records were captured
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Forget all the captured records
+    pub fn clear(&self) {
+        self.lock().clear()
+    }
+
+    /// Panic, listing captured records, unless a record matching
+    /// `expectation` was captured
+    pub fn assert_logged(&self, expectation: &Expectation) {
+        let records = self.lock();
+        if !records.iter().any(|r| expectation.matches(r)) {
+            panic!(
+                "no record matching {:?} was logged; captured records: {:#?}",
+                expectation, *records
+            );
+        }
+    }
+}
+
+impl Drain for Capture {
+    type Ok = ();
+    type Err = Error;
+    fn log(
+        &self,
+        record: &Record,
+        logger_values: &OwnedKVList,
+    ) -> result::Result<Self::Ok, Self::Err> {
+        let captured = CapturedRecord {
+            location: *record.location(),
+            tag: record.tag().to_string(),
+            level: record.level(),
+            msg: record.msg().to_string(),
+            kv: serialize_kv(record, &record.kv())?,
+            logger_kv: serialize_kv(record, logger_values)?,
+        };
+        self.lock().push(captured);
+        Ok(())
+    }
+    fn is_interest_static(&self) -> bool {
+        true
+    }
+}
+
+/// Assert that a `Capture` captured a matching record
+///
+/// Takes a `Capture`, a `Level`, optionally a message, as `msg == "exact"`
+/// or `msg ~= "part"`, and key-value pairs, and panics (listing all the
+/// captured records) unless some record matches all of them. See
+/// `testing::Expectation`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate slog;
+///
+/// use slog::testing::Capture;
+/// use slog::*;
+///
+/// fn main() {
+///     let capture = Capture::new();
+///     let log = Logger::root(capture.clone().fuse(), o!());
+///     warn!(log, "slow query"; "ms" => 1500, "table" => "users");
+///
+///     assert_logged!(capture, Level::Warning, msg == "slow query");
+///     assert_logged!(capture, Level::Warning, "table" => "users", "ms" => 1500);
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_logged(
+    ($capture:expr, $lvl:expr, msg ~= $msg:expr $(, $k:expr => $v:expr)* $(,)*) => {
+        $capture.assert_logged(
+            &$crate::testing::Expectation::new($lvl)
+                .msg_contains($msg)
+                $(.kv($k, $v))*
+        )
+    };
+    ($capture:expr, $lvl:expr, msg == $msg:expr $(, $k:expr => $v:expr)* $(,)*) => {
+        $capture.assert_logged(
+            &$crate::testing::Expectation::new($lvl)
+                .msg($msg)
+                $(.kv($k, $v))*
+        )
+    };
+    ($capture:expr, $lvl:expr $(, $k:expr => $v:expr)* $(,)*) => {
+        $capture.assert_logged(
+            &$crate::testing::Expectation::new($lvl)
+                $(.kv($k, $v))*
+        )
+    };
+);
@@ -19,6 +19,7 @@ mod no_imports {
 mod std_only {
     use super::super::*;
     use std;
+    use std::vec::Vec;
 
     #[test]
     fn logger_fmt_debug_sanity() {
@@ -59,6 +60,2130 @@ mod std_only {
         let log = Logger::root(log, o!("d1" => "dd", "d2" => "dd"));
         info!(log, "(d2, d1, c, b2, b1, a)");
     }
+
+    /// Drain counting records that reached it
+    #[derive(Clone)]
+    struct Counter(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Counter {
+        fn new() -> Self {
+            Counter(Arc::new(std::sync::atomic::AtomicUsize::new(0)))
+        }
+
+        fn count(&self) -> usize {
+            self.0.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl Drain for Counter {
+        type Ok = ();
+        type Err = Never;
+        fn log(
+            &self,
+            _: &Record,
+            _: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Drain collecting messages and key-value pairs of records that
+    /// reached it
+    #[derive(Clone)]
+    struct Collector(Arc<std::sync::Mutex<Vec<(String, String)>>>);
+
+    impl Collector {
+        fn new() -> Self {
+            Collector(Arc::new(std::sync::Mutex::new(vec![])))
+        }
+
+        fn messages(&self) -> Vec<String> {
+            self.0.lock().unwrap().iter().map(|e| e.0.clone()).collect()
+        }
+
+        fn kvs(&self) -> Vec<String> {
+            self.0.lock().unwrap().iter().map(|e| e.1.clone()).collect()
+        }
+    }
+
+    impl Drain for Collector {
+        type Ok = ();
+        type Err = Never;
+        fn log(
+            &self,
+            record: &Record,
+            values: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            let mut kv = vec![];
+            {
+                let mut ser = AsFmtSerializer(|k, v| {
+                    kv.push(format!("{}={}", k, v));
+                    Ok(())
+                });
+                record.kv().serialize(record, &mut ser).unwrap();
+                values.serialize(record, &mut ser).unwrap();
+            }
+            self.0
+                .lock()
+                .unwrap()
+                .push((format!("{}", record.msg()), kv.join(", ")));
+            Ok(())
+        }
+        fn is_interest_static(&self) -> bool {
+            true
+        }
+    }
+
+    /// Collector failing while its switch is on
+    #[derive(Clone)]
+    struct Flaky(Collector, Arc<std::sync::atomic::AtomicBool>);
+
+    impl Flaky {
+        fn new() -> Self {
+            Flaky(
+                Collector::new(),
+                Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            )
+        }
+
+        fn fail(&self, fail: bool) {
+            self.1.store(fail, std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    impl Drain for Flaky {
+        type Ok = ();
+        type Err = &'static str;
+        fn log(
+            &self,
+            record: &Record,
+            values: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            if self.1.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err("failed");
+            }
+            let _ = self.0.log(record, values);
+            Ok(())
+        }
+    }
+
+    /// Collector failing records with a key-value pair with its key, eg.
+    /// summaries of other drains
+    #[derive(Clone)]
+    struct FailingKey(Collector, &'static str);
+
+    impl Drain for FailingKey {
+        type Ok = ();
+        type Err = &'static str;
+        fn log(
+            &self,
+            record: &Record,
+            values: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            let scratch = Collector::new();
+            let _ = scratch.log(record, values);
+            let prefix = format!("{}=", self.1);
+            if scratch.kvs()[0].split(", ").any(|kv| kv.starts_with(&prefix)) {
+                return Err("failed");
+            }
+            let _ = self.0.log(record, values);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn sample_keeps_levels_with_full_rate() {
+        let counter = Counter::new();
+        let drain = Arc::new(
+            counter
+                .clone()
+                .sample(0.0)
+                .level_rate(Level::Error, 1.0)
+                .seed(7),
+        );
+        let log = Logger::root(drain.clone().fuse(), o!());
+
+        for _ in 0..100 {
+            error!(log, "kept");
+            info!(log, "dropped");
+        }
+
+        assert_eq!(counter.count(), 100);
+        // `Sample` is not enabled for `Info`, so the records are skipped by
+        // the logging statement, and never reach it
+        assert_eq!(drain.dropped(), 0);
+        assert!(drain.is_enabled(Level::Error));
+        assert!(!drain.is_enabled(Level::Info));
+    }
+
+    #[test]
+    fn sample_keeps_errors_by_default() {
+        let counter = Counter::new();
+        let log = Logger::root(counter.clone().sample(0.0).fuse(), o!());
+
+        for _ in 0..10 {
+            crit!(log, "kept");
+            error!(log, "kept");
+            warn!(log, "dropped");
+            info!(log, "dropped");
+        }
+
+        assert_eq!(counter.count(), 20);
+    }
+
+    #[test]
+    fn sample_is_deterministic_with_seed() {
+        let run = |seed| {
+            let counter = Counter::new();
+            let log = Logger::root(
+                counter.clone().sample(0.25).seed(seed).fuse(),
+                o!(),
+            );
+            for _ in 0..1000 {
+                info!(log, "sampled");
+            }
+            counter.count()
+        };
+
+        let kept = run(42);
+        assert_eq!(kept, run(42));
+        assert!(kept > 150 && kept < 350, "kept {}", kept);
+    }
+
+    #[test]
+    fn rate_limit_per_call_site() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            RateLimit::new(collector.clone(), 1).burst(3).fuse(),
+            o!(),
+        );
+
+        for i in 0..10 {
+            warn!(log, "hot {}", i);
+            info!(log, "cold {}", i);
+            if i % 4 == 0 {
+                info!(log, "rare {}", i);
+            }
+        }
+        drop(log);
+
+        let messages = collector.messages();
+        let hot: Vec<_> =
+            messages.iter().filter(|m| m.starts_with("hot")).collect();
+        let rare: Vec<_> =
+            messages.iter().filter(|m| m.starts_with("rare")).collect();
+        assert_eq!(hot, ["hot 0", "hot 1", "hot 2"]);
+        assert_eq!(rare, ["rare 0", "rare 4", "rare 8"]);
+
+        let summaries: Vec<_> = messages
+            .iter()
+            .filter(|m| m.starts_with("suppressed 7 records from"))
+            .collect();
+        assert_eq!(summaries.len(), 2);
+    }
+
+    #[test]
+    fn rate_limit_summary_after_refill() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            RateLimit::new(collector.clone(), 20).burst(1).fuse(),
+            o!(),
+        );
+
+        let line = line!() + 3;
+        for _ in 0..3 {
+            for i in 0..5 {
+                warn!(log, "spam {}", i);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+
+        let messages = collector.messages();
+        assert_eq!(messages[0], "spam 0");
+        assert!(messages[1].starts_with("suppressed 4 records from"));
+        assert!(messages[1].ends_with(&format!(":{}", line)));
+        assert_eq!(messages[2], "spam 0");
+    }
+
+    #[test]
+    fn rate_limit_summary_from_other_call_site() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            RateLimit::new(collector.clone(), 20).burst(1).fuse(),
+            o!(),
+        );
+
+        let line = line!() + 2;
+        for i in 0..5 {
+            warn!(log, "burst {}", i);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        info!(log, "unrelated");
+
+        let messages = collector.messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], "burst 0");
+        assert!(messages[1].starts_with("suppressed 4 records from"));
+        assert!(messages[1].ends_with(&format!(":{}", line)));
+        assert_eq!(messages[2], "unrelated");
+    }
+
+    #[test]
+    fn rate_limit_passes_record_after_failed_summary() {
+        let failing = FailingKey(Collector::new(), "suppressed");
+        let drain = RateLimit::new(failing.clone(), 20).burst(1);
+        let values = OwnedKVList::from(OwnedKV(()));
+        let rs = record_static!(Level::Warning, "");
+        let log = || {
+            drain.log(&Record::new(&rs, &format_args!("spam"), b!()), &values)
+        };
+
+        assert_eq!(log(), Ok(Some(())));
+        assert_eq!(log(), Ok(None));
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        assert_eq!(log(), Err("failed"));
+        assert_eq!(failing.0.messages(), ["spam", "spam"]);
+    }
+
+    #[test]
+    fn rate_limit_by_tag() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            RateLimit::new(collector.clone(), 1).by_tag().fuse(),
+            o!(),
+        );
+
+        info!(log, #"db", "first");
+        info!(log, #"db", "second");
+        info!(log, #"net", "third");
+        assert_eq!(collector.messages(), ["first", "third"]);
+
+        log.flush().unwrap();
+        assert_eq!(
+            collector.messages()[2],
+            "suppressed 1 records tagged \"db\""
+        );
+    }
+
+    #[test]
+    fn owned_record_roundtrip() {
+        let collector = Collector::new();
+        let owned = std::sync::Mutex::new(None);
+        {
+            let drain = Filter::new(Discard, |_| false);
+            let log = Logger::root(drain.fuse(), o!("a" => 1));
+            let record_static = record_static!(Level::Info, "tag");
+            let values = log.list().clone();
+            *owned.lock().unwrap() = Some(
+                OwnedRecord::new(
+                    &Record::new(
+                        &record_static,
+                        &format_args!("msg {}", 1),
+                        b!("b" => "x", "c" => 1.5, "d" => None::<u8>),
+                    ),
+                    &values,
+                ).unwrap(),
+            );
+        }
+
+        let owned = owned.into_inner().unwrap().unwrap();
+        assert_eq!(owned.msg(), "msg 1");
+        assert_eq!(owned.tag(), "tag");
+        assert_eq!(owned.level(), Level::Info);
+        assert_eq!(owned.kv()[0].1, OwnedValue::None);
+        assert_eq!(owned.kv()[1].1, OwnedValue::F64(1.5));
+        assert_eq!(owned.kv()[2].1, OwnedValue::Str("x".into()));
+        assert_eq!(owned.serialize_logger_values().unwrap()[0].1,
+                   OwnedValue::I32(1));
+
+        owned.log_to(&collector).unwrap();
+        assert_eq!(collector.messages(), ["msg 1"]);
+        assert_eq!(collector.kvs(), ["d=, c=1.5, b=x, a=1"]);
+    }
+
+    #[test]
+    fn dedup_collapses_repetitions() {
+        let collector = Collector::new();
+        let log = Logger::root(Dedup::new(collector.clone()).fuse(), o!());
+
+        for _ in 0..2 {
+            for _ in 0..5 {
+                warn!(log, "reconnecting"; "peer" => "db1");
+            }
+            warn!(log, "reconnecting"; "peer" => "db2");
+        }
+        info!(log, "connected");
+
+        assert_eq!(
+            collector.messages(),
+            [
+                "reconnecting",
+                "reconnecting",
+                "reconnecting",
+                "reconnecting",
+                "reconnecting",
+                "reconnecting",
+                "connected",
+            ]
+        );
+        assert_eq!(
+            collector.kvs(),
+            [
+                "peer=db1",
+                "repeated=4, peer=db1",
+                "peer=db2",
+                "peer=db1",
+                "repeated=4, peer=db1",
+                "peer=db2",
+                "",
+            ]
+        );
+    }
+
+    #[test]
+    fn dedup_flushes_periodically() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            Dedup::new(collector.clone()).max_repeats(3).fuse(),
+            o!("a" => "b"),
+        );
+
+        for _ in 0..8 {
+            info!(log, "same");
+        }
+        assert_eq!(collector.kvs(), ["a=b", "repeated=3, a=b", "repeated=3, a=b"]);
+
+        drop(log);
+        assert_eq!(
+            collector.kvs(),
+            ["a=b", "repeated=3, a=b", "repeated=3, a=b", "repeated=1, a=b"]
+        );
+    }
+
+    #[test]
+    fn dedup_passes_expired_repetitions() {
+        let collector = Collector::new();
+        let drain = Arc::new(
+            Dedup::new(collector.clone())
+                .flush_timeout(std::time::Duration::from_millis(20)),
+        );
+        Dedup::flush_periodically(
+            &drain,
+            std::time::Duration::from_millis(10),
+        );
+        let log = Logger::root(drain.fuse(), o!());
+
+        for _ in 0..5 {
+            warn!(log, "reconnecting");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        assert_eq!(collector.kvs(), ["", "repeated=4"]);
+    }
+
+    #[test]
+    fn dedup_keeps_count_on_error() {
+        let flaky = Flaky::new();
+        let log = Logger::root(
+            Dedup::new(flaky.clone()).max_repeats(3).ignore_res(),
+            o!(),
+        );
+
+        for i in 0..5 {
+            // fail passing the third repetition
+            flaky.fail(i == 3);
+            info!(log, "same");
+        }
+        assert_eq!(flaky.0.messages(), ["same", "same"]);
+        assert_eq!(flaky.0.kvs(), ["", "repeated=4"]);
+    }
+
+    #[test]
+    fn dedup_passes_record_after_failed_summary() {
+        let failing = FailingKey(Collector::new(), "repeated");
+        let drain = Dedup::new(failing.clone());
+        let values = OwnedKVList::from(OwnedKV(()));
+        let rs = record_static!(Level::Info, "");
+        let log = |msg: &str| {
+            drain.log(&Record::new(&rs, &format_args!("{}", msg), b!()), &values)
+        };
+
+        assert_eq!(log("same"), Ok(Some(())));
+        assert_eq!(log("same"), Ok(None));
+        assert_eq!(log("other"), Err("failed"));
+        // the new record replaced the failed one
+        assert_eq!(log("other"), Ok(None));
+        assert_eq!(failing.0.messages(), ["same", "other"]);
+    }
+
+    #[test]
+    fn flight_recorder_dumps_history_on_error() {
+        let collector = Collector::new();
+        let log = Logger::root(
+            FlightRecorder::new(collector.clone(), 3, Level::Info).fuse(),
+            o!("a" => 1),
+        );
+
+        for i in 0..5 {
+            debug!(log, "debug {}", i; "i" => i);
+        }
+        info!(log, "info");
+        assert_eq!(collector.messages(), ["info"]);
+
+        let log = log.new(o!("b" => 2));
+        error!(log, "error");
+        assert_eq!(
+            collector.messages(),
+            ["info", "debug 2", "debug 3", "debug 4", "error"]
+        );
+        assert_eq!(collector.kvs()[1], "i=2, a=1");
+        assert_eq!(collector.kvs()[4], "b=2, a=1");
+
+        // history is passed only once
+        error!(log, "error");
+        assert_eq!(collector.messages().len(), 6);
+    }
+
+    #[test]
+    fn directives_parse() {
+        let d: Directives = "info, hyper=warn,myapp::db=trace,myapp=off"
+            .parse()
+            .unwrap();
+        assert_eq!(d.level_for("other"), FilterLevel::Info);
+        assert_eq!(d.level_for("hyper"), FilterLevel::Warning);
+        assert_eq!(d.level_for("hyper::client"), FilterLevel::Warning);
+        assert_eq!(d.level_for("hyperx"), FilterLevel::Info);
+        assert_eq!(d.level_for("myapp::db::pool"), FilterLevel::Trace);
+        assert_eq!(d.level_for("myapp::web"), FilterLevel::Off);
+
+        let d: Directives = "hyper=debug".parse().unwrap();
+        assert_eq!(d.level_for("other"), FilterLevel::Off);
+
+        let d: Directives = "info,hyper".parse().unwrap();
+        assert_eq!(d.level_for("other"), FilterLevel::Info);
+        assert_eq!(d.level_for("hyper::client"), FilterLevel::Trace);
+
+        assert_eq!(
+            "info,=warn".parse::<Directives>(),
+            Err(DirectivesError::Directive("=warn".into()))
+        );
+        assert!("hyper=".parse::<Directives>().is_err());
+        assert_eq!(
+            "hyper=loud".parse::<Directives>(),
+            Err(DirectivesError::Level("loud".into()))
+        );
+    }
+
+    #[test]
+    fn directive_filter() {
+        let collector = Collector::new();
+        let directives: Directives =
+            "warn,slog::tests::std_only=info".parse().unwrap();
+        let drain = DirectiveFilter::new(collector.clone(), directives);
+        assert!(drain.is_enabled(Level::Info));
+        assert!(!drain.is_enabled(Level::Debug));
+
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "logged");
+        debug!(log, "filtered");
+        assert_eq!(collector.messages(), ["logged"]);
+
+        std::env::set_var("SLOG_TEST_DIRECTIVES", "error,slog::tests=debug");
+        let drain =
+            DirectiveFilter::from_env(Discard, "SLOG_TEST_DIRECTIVES").unwrap();
+        assert_eq!(
+            drain.directives().level_for("slog::tests::std_only"),
+            FilterLevel::Debug
+        );
+        let drain = DirectiveFilter::from_env(Discard, "SLOG_TEST_UNSET_VAR")
+            .unwrap();
+        assert_eq!(drain.directives().level_for("slog"), FilterLevel::Error);
+    }
+
+    #[test]
+    fn atomic_switch_finishes_in_flight_records_on_old_drain() {
+        use std::sync::Barrier;
+
+        struct Blocking(Collector, Arc<Barrier>);
+
+        impl Drain for Blocking {
+            type Ok = ();
+            type Err = Never;
+            fn log(
+                &self,
+                record: &Record,
+                values: &OwnedKVList,
+            ) -> std::result::Result<Self::Ok, Self::Err> {
+                self.1.wait();
+                self.1.wait();
+                self.0.log(record, values)
+            }
+        }
+
+        let (old, new) = (Collector::new(), Collector::new());
+        let barrier = Arc::new(Barrier::new(2));
+        let switch = AtomicSwitch::new(Blocking(old.clone(), barrier.clone()));
+        let ctrl = switch.ctrl();
+        let log = Logger::root(switch, o!());
+
+        let thread = {
+            let log = log.clone();
+            std::thread::spawn(move || info!(log, "in flight"))
+        };
+        barrier.wait();
+        ctrl.set(new.clone());
+        info!(log, "after switch");
+        barrier.wait();
+        thread.join().unwrap();
+
+        assert_eq!(old.messages(), ["in flight"]);
+        assert_eq!(new.messages(), ["after switch"]);
+    }
+
+    #[test]
+    fn dynamic_level_filter() {
+        let collector = Collector::new();
+        let drain = DynamicLevelFilter::new(collector.clone(), Level::Info);
+        let level = drain.handle();
+        let log = Logger::root(drain.fuse(), o!());
+
+        debug!(log, "hidden");
+        info!(log, "shown");
+        assert!(!log.is_enabled(Level::Debug));
+
+        level.clone().set(Level::Debug);
+        assert_eq!(level.get(), Level::Debug);
+        assert!(log.is_enabled(Level::Debug));
+        debug!(log, "debug");
+        trace!(log, "trace");
+
+        level.set(Level::Error);
+        info!(log, "info");
+        assert_eq!(collector.messages(), ["shown", "debug"]);
+    }
+
+    #[test]
+    fn router_routes_by_tag() {
+        let (audit, perf, db, default) = (
+            Collector::new(),
+            Collector::new(),
+            Collector::new(),
+            Collector::new(),
+        );
+        let drain = Router::new()
+            .exact("audit", audit.clone())
+            .prefix("perf", perf.clone())
+            .prefix("perf::db", db.clone())
+            .default(default.clone());
+        let log = Logger::root(drain.fuse(), o!());
+
+        info!(log, #"audit", "audit");
+        info!(log, #"audit::x", "audit prefix");
+        info!(log, #"perf", "perf");
+        info!(log, #"perf::db::pool", "db");
+        info!(log, "untagged");
+
+        assert_eq!(audit.messages(), ["audit"]);
+        assert_eq!(perf.messages(), ["perf"]);
+        assert_eq!(db.messages(), ["db"]);
+        assert_eq!(default.messages(), ["audit prefix", "untagged"]);
+
+        let drain = Router::new().exact("audit", audit.clone());
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "dropped");
+        assert_eq!(default.messages().len(), 2);
+        assert_eq!(audit.messages(), ["audit"]);
+    }
+
+    #[test]
+    fn broadcast_error_policies() {
+        let (ignore, propagate, disable) =
+            (Flaky::new(), Flaky::new(), Flaky::new());
+        let drain = Broadcast::new()
+            .add(ignore.clone(), ErrorPolicy::Ignore)
+            .add(propagate.clone(), ErrorPolicy::Propagate)
+            .add(disable.clone(), ErrorPolicy::DisableAfter(2))
+            .add(
+                Collector::new().filter_level(Level::Info).map_err(|_| ""),
+                ErrorPolicy::Ignore,
+            );
+        assert_eq!(drain.len(), 4);
+        let log = Logger::root(drain.ignore_res(), o!());
+        info!(log, "first");
+
+        ignore.fail(true);
+        disable.fail(true);
+        info!(log, "second");
+        disable.fail(false);
+        info!(log, "third");
+        disable.fail(true);
+        info!(log, "fourth");
+        info!(log, "fifth");
+        disable.fail(false);
+        info!(log, "sixth");
+
+        assert_eq!(ignore.0.messages(), ["first"]);
+        assert_eq!(
+            propagate.0.messages(),
+            ["first", "second", "third", "fourth", "fifth", "sixth"]
+        );
+        assert_eq!(disable.0.messages(), ["first", "third"]);
+
+        let drain = Broadcast::new()
+            .add(ignore.clone(), ErrorPolicy::Ignore)
+            .add(propagate.clone(), ErrorPolicy::Propagate)
+            .add(disable.clone(), ErrorPolicy::DisableAfter(1));
+        let values = o!().into();
+        let log_to = |drain: &Broadcast<_>| {
+            drain.log(&record!(Level::Info, "", &format_args!("x"), b!()), &values)
+        };
+        assert_eq!(log_to(&drain), Ok(()));
+        propagate.fail(true);
+        disable.fail(true);
+        assert_eq!(log_to(&drain), Err("failed"));
+        assert!(drain.is_disabled(2));
+        assert!(drain.is_enabled(Level::Info));
+
+        let drain = Broadcast::new()
+            .add(disable.clone(), ErrorPolicy::DisableAfter(1))
+            .add(
+                Collector::new().filter_level(Level::Info).map_err(|_| ""),
+                ErrorPolicy::Ignore,
+            );
+        assert!(drain.is_enabled(Level::Debug));
+        let _ = log_to(&drain);
+        assert!(!drain.is_enabled(Level::Debug));
+        assert!(drain.is_enabled(Level::Info));
+    }
+
+    /// Collector announcing each record, then waiting for permission to
+    /// collect it
+    struct Gated {
+        collector: Collector,
+        started: std::sync::Mutex<std::sync::mpsc::Sender<()>>,
+        go: std::sync::Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl Drain for Gated {
+        type Ok = ();
+        type Err = Never;
+        fn log(
+            &self,
+            record: &Record,
+            values: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            let _ = self.started.lock().unwrap().send(());
+            let _ = self.go.lock().unwrap().recv();
+            self.collector.log(record, values)
+        }
+    }
+
+    /// Create `Async` over a `Gated` collector, with the worker blocked on
+    /// the first record, and the channel full
+    fn blocked_async(
+        overflow: OverflowPolicy,
+    ) -> (Logger, Collector, std::sync::mpsc::Sender<()>) {
+        let collector = Collector::new();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (go_tx, go_rx) = std::sync::mpsc::channel();
+        let drain = Async::new(Gated {
+            collector: collector.clone(),
+            started: std::sync::Mutex::new(started_tx),
+            go: std::sync::Mutex::new(go_rx),
+        })
+        .chan_size(2)
+        .overflow(overflow)
+        .build();
+        let log = Logger::root(drain.fuse(), o!("x" => 1));
+        info!(log, "0");
+        started_rx.recv().unwrap();
+        info!(log, "1");
+        info!(log, "2");
+        (log, collector, go_tx)
+    }
+
+    #[test]
+    fn async_passes_records_and_flushes_on_drop() {
+        let collector = Collector::new();
+        let drain = Async::new(collector.clone()).chan_size(4).build();
+        let log = Logger::root(drain.fuse(), o!("x" => 1));
+        for i in 0..100 {
+            info!(log, "{}", i; "i" => i);
+        }
+        drop(log);
+        let expected: Vec<_> = (0..100).map(|i| format!("{}", i)).collect();
+        assert_eq!(collector.messages(), expected);
+        assert_eq!(collector.kvs()[7], "i=7, x=1");
+    }
+
+    #[test]
+    fn async_overflow_policies() {
+        let (log, collector, go) = blocked_async(OverflowPolicy::DropNewest);
+        info!(log, "3");
+        info!(log, "4");
+        drop(go);
+        drop(log);
+        assert_eq!(collector.messages(), ["0", "1", "2"]);
+
+        let (log, collector, go) = blocked_async(OverflowPolicy::DropOldest);
+        info!(log, "3");
+        info!(log, "4");
+        drop(go);
+        drop(log);
+        assert_eq!(collector.messages(), ["0", "3", "4"]);
+
+        let (log, collector, go) =
+            blocked_async(OverflowPolicy::DropBelow(Level::Warning));
+        info!(log, "3");
+        let blocked = {
+            let log = log.clone();
+            std::thread::spawn(move || warn!(log, "4"))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(collector.messages().is_empty());
+        drop(go);
+        blocked.join().unwrap();
+        drop(log);
+        assert_eq!(collector.messages(), ["0", "1", "2", "4"]);
+    }
+
+    #[test]
+    fn async_counts_dropped() {
+        let collector = Collector::new();
+        let (started_tx, started_rx) = std::sync::mpsc::channel();
+        let (go_tx, go_rx) = std::sync::mpsc::channel();
+        let drain = Async::new(Gated {
+            collector: collector.clone(),
+            started: std::sync::Mutex::new(started_tx),
+            go: std::sync::Mutex::new(go_rx),
+        })
+        .chan_size(1)
+        .overflow(OverflowPolicy::DropNewest)
+        .build();
+        let values = o!().into();
+        for i in 0..10 {
+            drain
+                .log(&record!(Level::Info, "", &format_args!("{}", i), b!()), &values)
+                .unwrap();
+            if i == 0 {
+                started_rx.recv().unwrap();
+            }
+        }
+        assert_eq!(drain.dropped(), 8);
+        drop(go_tx);
+        drop(drain);
+        assert_eq!(collector.messages(), ["0", "1"]);
+    }
+
+    /// Drain counting `flush` and `shutdown` calls, failing them on command
+    #[derive(Clone)]
+    struct Flushable {
+        flushed: Counter,
+        shut_down: Counter,
+        fail: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Flushable {
+        fn new() -> Self {
+            Flushable {
+                flushed: Counter::new(),
+                shut_down: Counter::new(),
+                fail: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+            }
+        }
+
+        fn result(&self, counter: &Counter) -> Result {
+            counter.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+                Err(Error::Other)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Drain for Flushable {
+        type Ok = ();
+        type Err = Never;
+        fn log(
+            &self,
+            _: &Record,
+            _: &OwnedKVList,
+        ) -> std::result::Result<Self::Ok, Self::Err> {
+            Ok(())
+        }
+        fn flush(&self) -> Result {
+            self.result(&self.flushed)
+        }
+        fn shutdown(&self) -> Result {
+            self.result(&self.shut_down)
+        }
+    }
+
+    #[test]
+    fn flush_passes_through_combinators() {
+        let (a, b) = (Flushable::new(), Flushable::new());
+        let drain = Duplicate(
+            a.clone().filter_level(Level::Info),
+            Box::new(b.clone().filter(|_| true)),
+        );
+        let drain = std::sync::Mutex::new(Arc::new(drain.map_err(|_| ())));
+        let log = Logger::root(drain.fuse(), o!());
+        let child = log.new(o!("child" => 1));
+
+        child.flush().unwrap();
+        assert_eq!((a.flushed.count(), b.flushed.count()), (1, 1));
+        Drain::shutdown(&log).unwrap();
+        assert_eq!((a.shut_down.count(), b.shut_down.count()), (1, 1));
+
+        b.fail.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(log.flush().is_err());
+        assert_eq!((a.flushed.count(), b.flushed.count()), (2, 2));
+
+        let log = Logger::root(b.clone().ignore_res(), o!());
+        assert!(log.flush().is_ok());
+        assert_eq!(b.flushed.count(), 3);
+    }
+
+    #[test]
+    fn flush_passes_pending_records() {
+        let collector = Collector::new();
+        let drain = Dedup::new(collector.clone());
+        let log = Logger::root(drain.fuse(), o!());
+        for _ in 0..3 {
+            info!(log, "again");
+        }
+        log.flush().unwrap();
+        assert_eq!(collector.kvs(), ["", "repeated=2"]);
+    }
+
+    #[test]
+    fn async_flush_and_shutdown() {
+        let (collector, flushable) = (Collector::new(), Flushable::new());
+        let drain =
+            Async::new(Duplicate(collector.clone(), flushable.clone()).fuse())
+                .chan_size(4)
+                .build();
+        let values = o!().into();
+        for i in 0..10 {
+            drain
+                .log(&record!(Level::Info, "", &format_args!("{}", i), b!()), &values)
+                .unwrap();
+        }
+        drain.flush().unwrap();
+        assert_eq!(collector.messages().len(), 10);
+        assert_eq!(flushable.flushed.count(), 1);
+
+        drain.shutdown().unwrap();
+        assert_eq!(flushable.shut_down.count(), 1);
+        match drain.log(&record!(Level::Info, "", &format_args!("late"), b!()), &values) {
+            Err(AsyncError::Disconnected) => {}
+            _ => panic!("expected disconnected"),
+        }
+        assert!(drain.flush().is_err());
+        drop(drain);
+        assert_eq!(flushable.flushed.count(), 1);
+        assert_eq!(collector.messages().len(), 10);
+    }
+
+    #[test]
+    fn kv_filter_matches_record_and_logger_values() {
+        let collector = Collector::new();
+        let drain = KVFilter::new(collector.clone())
+            .when("tenant", KVCondition::one_of(&["a", "b"]))
+            .when("user_id", KVCondition::Eq("1234".into()))
+            .always_pass(Level::Warning);
+        let log = Logger::root(drain.fuse(), o!("tenant" => "a"));
+
+        info!(log, "1"; "user_id" => 1234);
+        info!(log, "2"; "user_id" => "1234");
+        info!(log, "3"; "user_id" => 1);
+        info!(log, "4");
+        info!(log.new(o!("tenant" => "c")), "5"; "user_id" => 1234);
+        info!(log.new(o!("user_id" => 1234)), "6");
+        info!(log, "7"; "tenant" => "b", "user_id" => 1234);
+        // record value overrides the logger one
+        info!(log, "8"; "tenant" => "c", "user_id" => 1234);
+        warn!(log, "9");
+        assert_eq!(collector.messages(), ["1", "2", "6", "7", "9"]);
+
+        let collector = Collector::new();
+        let drain = KVFilter::new(collector.clone())
+            .when("ms", KVCondition::Ge(100.0))
+            .when("slow", KVCondition::Present)
+            .any();
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "1"; "ms" => 99.5);
+        info!(log, "2"; "ms" => 100);
+        info!(log, "3"; "ms" => "abc");
+        info!(log, "4"; "slow" => ());
+        info!(log, "5"; "ms" => 1, "slow" => true);
+        assert_eq!(collector.messages(), ["2", "4", "5"]);
+    }
+
+    #[test]
+    fn redactor_patterns() {
+        let redactor = Redactor::new(Redaction::Mask)
+            .key("Email")
+            .pattern("*pass*word*")
+            .pattern("auth_*")
+            .pattern("tok?n");
+        assert!(redactor.is_redacted("email"));
+        assert!(!redactor.is_redacted("emails"));
+        assert!(redactor.is_redacted("password"));
+        assert!(redactor.is_redacted("db_Pass_Word_2"));
+        assert!(redactor.is_redacted("AUTH_"));
+        assert!(!redactor.is_redacted("x_auth_token"));
+        assert!(redactor.is_redacted("tok?n"));
+        assert!(!redactor.is_redacted("token"));
+    }
+
+    #[test]
+    fn redact_record_and_logger_values() {
+        let collector = Collector::new();
+        let redactor = Redactor::new(Redaction::Mask).key("email");
+        let drain = Redact::new(collector.clone(), redactor);
+        let log = Logger::root(drain.fuse(), o!("email" => "a@b.c", "app" => 1));
+        info!(log.new(o!("user" => "bob")), "x"; "email" => "d@e.f", "n" => 2);
+        assert_eq!(
+            collector.kvs(),
+            ["n=2, email=[REDACTED], user=bob, app=1, email=[REDACTED]"]
+        );
+        assert_eq!(collector.messages(), ["x"]);
+
+        let collector = Collector::new();
+        let redactor = Redactor::new(Redaction::Remove).pattern("*secret*");
+        let drain = Redact::new(collector.clone(), redactor);
+        let log = Logger::root(drain.fuse(), o!("secret" => 1));
+        info!(log, "x"; "my_secret" => true, "n" => 2);
+        assert_eq!(collector.kvs(), ["n=2"]);
+
+        let collector = Collector::new();
+        let redactor = Redactor::new(Redaction::Hash("salt".into())).key("id");
+        let drain = Redact::new(collector.clone(), redactor);
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "x"; "id" => 1234);
+        info!(log, "x"; "id" => "1234");
+        info!(log, "x"; "id" => 1235);
+        let kvs = collector.kvs();
+        assert_eq!(kvs[0], kvs[1]);
+        assert!(kvs[0] != kvs[2]);
+        assert!(!kvs[0].contains("1234"));
+        assert_eq!(kvs[0].len(), "id=".len() + 16);
+
+        let collector = Collector::new();
+        let redactor = Redactor::new(Redaction::Hash("pepper".into())).key("id");
+        let drain = Redact::new(collector.clone(), redactor);
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "x"; "id" => 1234);
+        assert!(collector.kvs()[0] != kvs[0]);
+
+        // FNV-1a of "foobar"
+        let collector = Collector::new();
+        let redactor = Redactor::new(Redaction::Hash("foo".into())).key("id");
+        let drain = Redact::new(collector.clone(), redactor);
+        let log = Logger::root(drain.fuse(), o!("id" => "bar"));
+        info!(log, "x"; "id" => "bar");
+        assert_eq!(
+            collector.kvs(),
+            ["id=85944171f73967e8, id=85944171f73967e8"]
+        );
+    }
+
+    fn rewrite(rewriter: &KeyRewriter, key: &str) -> Option<String> {
+        use std::string::ToString;
+        rewriter.rewrite(key).map(|key| key.to_string())
+    }
+
+    #[test]
+    fn key_rewriter_rules() {
+        let rewriter = KeyRewriter::new()
+            .rename("a", "x")
+            .rename_with(|key| if key.len() > 1 {
+                Some(key.to_uppercase())
+            } else {
+                None
+            })
+            .prefix("p.");
+        assert_eq!(rewrite(&rewriter, "a").unwrap(), "p.x");
+        assert_eq!(rewrite(&rewriter, "bc").unwrap(), "p.BC");
+        assert_eq!(rewrite(&rewriter, "b").unwrap(), "p.b");
+        // cached
+        assert_eq!(rewrite(&rewriter, "bc").unwrap(), "p.BC");
+
+        let rewriter = KeyRewriter::new().rename("a", "x");
+        assert!(rewriter.rewrite("b").is_none());
+    }
+
+    #[test]
+    #[cfg(not(feature = "dynamic-keys"))]
+    fn key_rewriters_share_leaked_keys() {
+        let a = KeyRewriter::new().prefix("shared.").rewrite("key").unwrap();
+        let b = KeyRewriter::new().prefix("shared.").rewrite("key").unwrap();
+        assert_eq!(a, "shared.key");
+        assert_eq!(a.as_ptr(), b.as_ptr());
+    }
+
+    #[test]
+    fn rewrite_keys_of_record_and_logger() {
+        let collector = Collector::new();
+        let rewriter = KeyRewriter::new().rename("user", "user.name");
+        let drain = RewriteKeys::new(collector.clone(), rewriter);
+        let log = Logger::root(drain.fuse(), o!("user" => "bob", "app" => 1));
+        info!(log, "x"; "user" => "alice", "n" => 2);
+        assert_eq!(
+            collector.kvs(),
+            ["n=2, user.name=alice, app=1, user.name=bob"]
+        );
+        assert_eq!(collector.messages(), ["x"]);
+    }
+
+    #[test]
+    fn prefix_child_logger_keys() {
+        let collector = Collector::new();
+        let log = Logger::root(collector.clone().fuse(), o!("app" => 1));
+        let db = log.new(Prefixed::new("db.", o!("host" => "db1")));
+        info!(db, "x"; "n" => 2);
+        assert_eq!(collector.kvs(), ["n=2, db.host=db1, app=1"]);
+
+        let collector = Collector::new();
+        let log = Logger::root(collector.clone().fuse(), o!("app" => 1));
+        let rewriter = KeyRewriter::new().prefix("db.");
+        let db = Logger::root(RewriteKeys::new(log, rewriter).fuse(), o!());
+        info!(db, "x"; "n" => 2);
+        assert_eq!(collector.kvs(), ["db.n=2, app=1"]);
+    }
+
+    #[test]
+    fn enrich_record_values() {
+        let collector = Collector::new();
+        let drain = Enrich::new(collector.clone())
+            .provider(ProcessId::new("pid"))
+            .provider(Hostname::with_hostname("host", Some("h1".into())))
+            .provider(Hostname::with_hostname("none", None))
+            .provider(|record: &Record, serializer: &mut dyn Serializer| {
+                serializer.emit_str(key::key_from_static("level"), record.level().as_str())
+            });
+        let log = Logger::root(drain.fuse(), o!("app" => 1));
+        info!(log, "x"; "n" => 2);
+        assert_eq!(
+            collector.kvs(),
+            [format!(
+                "n=2, pid={}, host=h1, none=, level=INFO, app=1",
+                std::process::id()
+            )]
+        );
+        assert_eq!(collector.messages(), ["x"]);
+    }
+
+    #[test]
+    fn enrich_thread_and_time() {
+        let collector = Collector::new();
+        let drain = Enrich::new(collector.clone())
+            .provider(ThreadName::new("thread"))
+            .provider(ThreadId::new("thread_id"))
+            .provider(Uptime::new("uptime"))
+            .provider(Timestamp::new("ts"));
+        let log = Logger::root(drain.fuse(), o!());
+        std::thread::Builder::new()
+            .name("worker".into())
+            .spawn(move || info!(log, "x"))
+            .unwrap()
+            .join()
+            .unwrap();
+        let kvs = &collector.kvs()[0];
+        let kvs: Vec<_> = kvs.split(", ").collect();
+        assert_eq!(kvs[0], "thread=worker");
+        let thread_id: usize = kvs[1]["thread_id=".len()..].parse().unwrap();
+        assert!(thread_id > 0);
+        let uptime: f64 = kvs[2]["uptime=".len()..].parse().unwrap();
+        assert!((0.0..60.0).contains(&uptime));
+        // 2018-03-28T12:34:56.789012Z
+        let ts = &kvs[3]["ts=".len()..];
+        assert_eq!(ts.len(), 27);
+        assert!(ts.starts_with("20") && ts.ends_with('Z'));
+        assert_eq!(&ts[10..11], "T");
+    }
+
+    #[test]
+    fn enrich_uptime_is_process_wide() {
+        let first = Uptime::new("first");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let collector = Collector::new();
+        let drain = Enrich::new(collector.clone())
+            .provider(Uptime::new("second"))
+            .provider(first);
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "x");
+        let kvs = &collector.kvs()[0];
+        let uptimes: Vec<f64> = kvs
+            .split(", ")
+            .map(|kv| kv.split('=').nth(1).unwrap().parse().unwrap())
+            .collect();
+        assert!(uptimes[0] >= 0.02);
+        assert!(uptimes[1] >= uptimes[0]);
+    }
+
+    #[test]
+    fn rfc3339_dates() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let format = |secs, micros| {
+            let time = UNIX_EPOCH
+                + Duration::from_secs(secs)
+                + Duration::from_micros(micros);
+            format!("{}", enrich::Rfc3339(time))
+        };
+        assert_eq!(format(0, 0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(format(951_827_696, 7), "2000-02-29T12:34:56.000007Z");
+        assert_eq!(format(1_522_240_496, 0), "2018-03-28T12:34:56.000000Z");
+        assert_eq!(format(4_107_542_399, 0), "2100-02-28T23:59:59.000000Z");
+    }
+
+    #[test]
+    fn stats_counts_records() {
+        let flaky = Flaky::new();
+        let drain = Stats::new(flaky.clone().filter_level(Level::Info));
+        let stats = drain.handle();
+        // debug records reach `Stats` through the other branch
+        let drain = Duplicate::new(drain, Counter::new());
+        let log = Logger::root(drain.ignore_res(), o!());
+        info!(log, "1");
+        error!(log, "2");
+        error!(log, "3");
+        debug!(log, "4");
+        let first = stats.snapshot();
+        assert_eq!(first.records(Level::Info), 1);
+        assert_eq!(first.records(Level::Error), 2);
+        assert_eq!(first.records(Level::Debug), 1);
+        assert_eq!(first.records(Level::Critical), 0);
+        assert_eq!(first.total(), 4);
+        assert_eq!(first.rejected(), 1);
+        assert_eq!(first.errors(), 0);
+        assert_eq!(flaky.0.messages(), ["1", "2", "3"]);
+
+        flaky.fail(true);
+        warn!(log, "5");
+        debug!(log, "6");
+        let second = stats.snapshot();
+        assert_eq!(second.total(), 6);
+        assert_eq!(second.errors(), 1);
+
+        let diff = second.since(&first);
+        assert_eq!(diff.records(Level::Warning), 1);
+        assert_eq!(diff.records(Level::Error), 0);
+        assert_eq!(diff.total(), 2);
+        assert_eq!(diff.errors(), 1);
+        assert_eq!(diff.rejected(), 1);
+    }
+
+    #[test]
+    fn stats_disabled_levels_never_reach_log() {
+        let collector = Collector::new();
+        let drain = Stats::new(collector.clone().filter_level(Level::Info));
+        let stats = drain.handle();
+        let log = Logger::root(drain.fuse(), o!());
+        for i in 0..10 {
+            debug!(log, "skipped"; "i" => i);
+        }
+        info!(log, "logged");
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.records(Level::Debug), 0);
+        assert_eq!(snapshot.total(), 1);
+        assert_eq!(snapshot.rejected(), 0);
+        assert_eq!(collector.messages(), ["logged"]);
+    }
+
+    #[test]
+    fn catch_unwind_returns_panics() {
+        let collector = Collector::new();
+        let drain = CatchUnwind::new(collector.clone()).disable_after(2);
+        let values = o!().into();
+        fn boom(_: &Record) -> &'static str {
+            panic!("boom")
+        }
+
+        match drain.log(&record!(Level::Info, "", &format_args!("1"), b!("x" => FnValue(boom))), &values) {
+            Err(CatchUnwindError::Panic(Some(ref msg))) if msg == "boom" => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(!drain.is_disabled());
+        assert!(drain.log(&record!(Level::Info, "", &format_args!("2"), b!()), &values).is_ok());
+        // only consecutive panics count
+        assert!(drain.log(&record!(Level::Info, "", &format_args!("3"), b!("x" => FnValue(boom))), &values).is_err());
+        assert!(!drain.is_disabled());
+        assert!(drain.log(&record!(Level::Info, "", &format_args!("4"), b!("x" => FnValue(boom))), &values).is_err());
+        assert!(drain.is_disabled());
+        assert!(!drain.is_enabled(Level::Critical));
+        match drain.log(&record!(Level::Info, "", &format_args!("5"), b!()), &values) {
+            Ok(None) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(collector.messages(), ["2"]);
+    }
+
+    #[test]
+    fn catch_unwind_passes_errors() {
+        let flaky = Flaky::new();
+        let drain = CatchUnwind::new(flaky.clone());
+        let values = o!().into();
+        flaky.fail(true);
+        match drain.log(&record!(Level::Info, "", &format_args!("x"), b!()), &values) {
+            Err(CatchUnwindError::Drain("failed")) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(drain.flush().is_ok());
+    }
+
+    #[test]
+    fn capture_records() {
+        let capture = testing::Capture::new();
+        let log = Logger::root(capture.clone().fuse(), o!("peer" => "db1"));
+        info!(log, "connected");
+        error!(log.new(o!("conn" => 7)), #"net", "read timeout"; "ms" => 1500, "retry" => true);
+        warn!(log, "slow"; "user_id" => None::<u32>);
+
+        assert_eq!(capture.len(), 3);
+        let errors = capture.records_at(Level::Error);
+        assert_eq!(errors.len(), 1);
+        let error = &errors[0];
+        assert_eq!(error.msg(), "read timeout");
+        assert_eq!(error.tag(), "net");
+        assert_eq!(error.file(), file!());
+        assert_eq!(error.get("ms"), Some(&OwnedValue::I32(1500)));
+        assert_eq!(error.get("retry"), Some(&OwnedValue::Bool(true)));
+        assert_eq!(error.get("conn"), Some(&OwnedValue::I32(7)));
+        assert_eq!(error.get("peer"), Some(&OwnedValue::Str("db1".into())));
+        assert_eq!(error.get("nope"), None);
+        assert_eq!(error.kv().len(), 2);
+        assert_eq!(error.logger_kv().len(), 2);
+
+        let with_user = capture.find_by_key("user_id");
+        assert_eq!(with_user.len(), 1);
+        assert_eq!(with_user[0].msg(), "slow");
+        assert_eq!(capture.find_by_key("peer").len(), 3);
+
+        assert_logged!(capture, Level::Error, msg ~= "timeout", "peer" => "db1");
+        assert_logged!(capture, Level::Error, msg == "read timeout", "ms" => 1500,);
+        assert_logged!(capture, Level::Info);
+        assert_logged!(capture, Level::Warning, "user_id" => "");
+
+        capture.clear();
+        assert!(capture.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "no record matching")]
+    fn assert_logged_fails() {
+        let capture = testing::Capture::new();
+        let log = Logger::root(capture.clone().fuse(), o!("peer" => "db1"));
+        error!(log, "read timeout");
+        assert_logged!(capture, Level::Error, msg ~= "timeout", "peer" => "db2");
+    }
+
+    #[test]
+    fn call_sites_are_registered() {
+        let log = Logger::root(Discard, o!());
+        let line = line!() + 1;
+        warn!(log, #"registry", "registered");
+
+        let call_site = call_sites()
+            .find(|cs| cs.record_static().location.line == line)
+            .expect("call site registered");
+        assert_eq!(call_site.level(), Level::Warning);
+        assert_eq!(call_site.record_static().tag, "registry");
+        assert_eq!(call_site.record_static().location.file, file!());
+    }
+
+    struct Asked(Arc<std::sync::atomic::AtomicUsize>, bool);
+
+    impl Drain for Asked {
+        type Ok = ();
+        type Err = Never;
+        fn log(
+            &self,
+            _: &Record,
+            _: &OwnedKVList,
+        ) -> std::result::Result<(), Never> {
+            Ok(())
+        }
+        fn is_enabled(&self, level: Level) -> bool {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            level.is_at_least(Level::Info)
+        }
+        fn is_interest_static(&self) -> bool {
+            self.1
+        }
+    }
+
+    #[test]
+    fn interest_is_cached_per_call_site() {
+        let asked = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let log = Logger::root(Asked(asked.clone(), true), o!());
+        let child = log.new(o!("child" => true));
+        for _ in 0..100 {
+            debug!(log, "skipped");
+            debug!(child, "skipped");
+        }
+        // other tests may invalidate the interest concurrently
+        let asked = asked.load(std::sync::atomic::Ordering::SeqCst);
+        assert!((1..20).contains(&asked), "asked {} times", asked);
+    }
+
+    #[test]
+    fn interest_is_not_cached_unless_static() {
+        let asked = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let log = Logger::root(Asked(asked.clone(), false), o!());
+        for _ in 0..100 {
+            debug!(log, "skipped");
+        }
+        assert_eq!(asked.load(std::sync::atomic::Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn dynamic_level_invalidates_interest() {
+        let collector = Collector::new();
+        let drain = DynamicLevelFilter::new(collector.clone(), Level::Info);
+        let level = drain.handle();
+        let log = Logger::root(drain.fuse(), o!());
+
+        for i in 0..2 {
+            debug!(log, "debug"; "i" => i);
+            if i == 0 {
+                level.set(Level::Debug);
+            }
+        }
+        assert_eq!(collector.kvs(), ["i=1"]);
+    }
+
+    /// `io::Write` into a shared buffer
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl SharedBuf {
+        fn lines(&self) -> Vec<String> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect()
+        }
+    }
+
+    impl std::io::Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_serializer_values() {
+        let mut serializer = json::Serializer::new();
+        let key = key::key_from_static;
+        serializer.emit_str(key("s"), "q\"b\\n\nt\tc\u{1}é").unwrap();
+        serializer.emit_u64(key("u64"), u64::MAX).unwrap();
+        serializer.emit_i64(key("i64"), i64::MIN).unwrap();
+        serializer.emit_f64(key("f"), 1.0).unwrap();
+        serializer.emit_f32(key("f32"), 0.5).unwrap();
+        serializer.emit_f64(key("big"), 1e300).unwrap();
+        serializer.emit_f64(key("nan"), f64::NAN).unwrap();
+        serializer.emit_f64(key("inf"), f64::INFINITY).unwrap();
+        serializer.emit_f64(key("-inf"), f64::NEG_INFINITY).unwrap();
+        serializer.emit_bool(key("b"), false).unwrap();
+        serializer.emit_char(key("c"), '"').unwrap();
+        serializer.emit_none(key("none")).unwrap();
+        serializer.emit_unit(key("unit")).unwrap();
+        serializer
+            .emit_arguments(key("a\"k"), &format_args!("{}\n", 42))
+            .unwrap();
+        assert_eq!(serializer.len(), 14);
+        assert_eq!(
+            serializer.finish(),
+            concat!(
+                r#"{"s":"q\"b\\n\nt\tc\u0001é","u64":18446744073709551615,"#,
+                r#""i64":-9223372036854775808,"f":1.0,"f32":0.5,"big":1e300,"#,
+                r#""nan":"NaN","inf":"Infinity","-inf":"-Infinity","b":false,"#,
+                r#""c":"\"","none":null,"unit":null,"a\"k":"42\n"}"#
+            )
+        );
+    }
+
+    #[test]
+    fn json_drain_writes_lines() {
+        let buf = SharedBuf::default();
+        let log = Logger::root(
+            json::Json::new(buf.clone()).fuse(),
+            o!("version" => "1.2"),
+        );
+        info!(log, "got \"{}\"", "it"; "n" => 1);
+
+        let lines = buf.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with(r#"{"ts":""#), "{}", lines[0]);
+        let end = lines[0].find("Z\",").expect("timestamp") + 3;
+        assert_eq!(
+            &lines[0][end..],
+            r#""level":"INFO","msg":"got \"it\"","n":1,"version":"1.2"}"#
+        );
+    }
+
+    #[test]
+    fn json_key_order_and_duplicates() {
+        use json::{DuplicateKeys, Json, KeyOrder};
+
+        let line = |configure: fn(Json<SharedBuf>) -> Json<SharedBuf>| {
+            let buf = SharedBuf::default();
+            let log = Logger::root(
+                configure(Json::new(buf.clone())).ts_key(None).fuse(),
+                o!("b" => "root", "msg" => "kv"),
+            );
+            let log = log.new(o!("c" => 3));
+            info!(log, "m"; "b" => "record", "a" => 1);
+            buf.lines().pop().unwrap()
+        };
+        assert_eq!(
+            line(|json| json),
+            r#"{"level":"INFO","msg":"m","a":1,"b":"record","c":3,"msg":"kv","b":"root"}"#
+        );
+        assert_eq!(
+            line(|json| json.duplicate_keys(DuplicateKeys::First)),
+            r#"{"level":"INFO","msg":"m","a":1,"b":"record","c":3}"#
+        );
+        assert_eq!(
+            line(|json| json.duplicate_keys(DuplicateKeys::Last)),
+            r#"{"level":"INFO","msg":"m","a":1,"c":3,"b":"root"}"#
+        );
+        assert_eq!(
+            line(|json| json.key_order(KeyOrder::Sorted)),
+            r#"{"level":"INFO","msg":"m","a":1,"b":"record","b":"root","c":3,"msg":"kv"}"#
+        );
+        assert_eq!(
+            line(|json| {
+                json.key_order(KeyOrder::LoggerFirst)
+                    .duplicate_keys(DuplicateKeys::First)
+                    .msg_key("message")
+                    .level_key("severity")
+            }),
+            r#"{"severity":"INFO","message":"m","c":3,"msg":"kv","a":1,"b":"record"}"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "nested-values")]
+    fn json_nested_values() {
+        use std::collections::BTreeMap;
+
+        struct Peer;
+
+        impl ::serde::Serialize for Peer {
+            fn serialize<S: ::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct;
+
+                let mut weights = BTreeMap::new();
+                weights.insert(1, Some(0.5));
+                weights.insert(2, None);
+                let mut peer = serializer.serialize_struct("Peer", 4)?;
+                peer.serialize_field("name", "db\"1")?;
+                peer.serialize_field("ports", &[80u16, 443])?;
+                peer.serialize_field("weights", &weights)?;
+                peer.serialize_field("unit", &())?;
+                peer.end()
+            }
+        }
+
+        impl Value for Peer {
+            fn serialize(
+                &self,
+                _record: &Record,
+                key: Key,
+                serializer: &mut dyn Serializer,
+            ) -> Result {
+                serializer.emit_serde(key, self)
+            }
+        }
+
+        impl SerdeValue for Peer {
+            fn as_serde(&self) -> &dyn erased_serde::Serialize {
+                self
+            }
+            fn to_sendable(&self) -> Box<dyn SerdeValue + Send + 'static> {
+                Box::new(Peer)
+            }
+        }
+
+        let mut serializer = json::Serializer::new();
+        Value::serialize(
+            &Peer,
+            &record!(Level::Info, "", &format_args!(""), b!()),
+            key::key_from_static("peer"),
+            &mut serializer,
+        )
+        .unwrap();
+        assert_eq!(
+            serializer.finish(),
+            r#"{"peer":{"name":"db\"1","ports":[80,443],"weights":{"1":0.5,"2":null},"unit":null}}"#
+        );
+    }
+
+    #[test]
+    fn logfmt_serializer_values() {
+        let mut serializer = logfmt::Serializer::new();
+        let key = key::key_from_static;
+        serializer.emit_str(key("plain"), "/users").unwrap();
+        serializer.emit_str(key("space"), "a b").unwrap();
+        serializer.emit_str(key("eq"), "a=b").unwrap();
+        serializer.emit_str(key("quote"), "say \"hi\"").unwrap();
+        serializer.emit_str(key("ctrl"), "a\\b\nc\td\u{1}").unwrap();
+        serializer.emit_str(key("empty"), "").unwrap();
+        serializer.emit_str(key("bad key="), "é").unwrap();
+        serializer.emit_u64(key("u64"), u64::MAX).unwrap();
+        serializer.emit_i8(key("i8"), -8).unwrap();
+        serializer.emit_f64(key("f"), 1.0).unwrap();
+        serializer.emit_f32(key("nan"), f32::NAN).unwrap();
+        serializer.emit_bool(key("ok"), true).unwrap();
+        serializer.emit_char(key("c"), ' ').unwrap();
+        serializer.emit_none(key("none")).unwrap();
+        serializer.emit_unit(key("unit")).unwrap();
+        serializer
+            .emit_arguments(key("args"), &format_args!("{} {}", 1, 2))
+            .unwrap();
+        assert_eq!(
+            serializer.finish(),
+            concat!(
+                r#"plain=/users space="a b" eq="a=b" quote="say \"hi\"" "#,
+                r#"ctrl="a\\b\nc\td\u0001" empty="" bad_key_=é "#,
+                r#"u64=18446744073709551615 i8=-8 f=1.0 nan=NaN ok=true "#,
+                r#"c=" " none= unit=() args="1 2""#
+            )
+        );
+    }
+
+    #[test]
+    fn logfmt_drain_writes_lines() {
+        let buf = SharedBuf::default();
+        let drain = logfmt::Logfmt::new(buf.clone())
+            .ts_key(None)
+            .msg_key("message");
+        let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+        info!(log, "request done"; "path" => "/users", "status" => 200);
+        warn!(log, "slow");
+
+        assert_eq!(
+            buf.lines(),
+            [
+                "level=INFO message=\"request done\" status=200 path=/users version=1.2",
+                "level=WARN message=slow version=1.2",
+            ]
+        );
+
+        let buf = SharedBuf::default();
+        let log = Logger::root(logfmt::Logfmt::new(buf.clone()).fuse(), o!());
+        info!(log, "with timestamp");
+        let line = buf.lines().pop().unwrap();
+        assert!(line.starts_with("ts=") && line.contains("Z level=INFO"), "{}", line);
+    }
+
+    /// Priority with version, timestamp, and the rest of a syslog message
+    fn split_syslog(msg: &str) -> (String, String, String) {
+        let mut parts = msg.splitn(3, ' ').map(String::from);
+        let pri = parts.next().unwrap();
+        let ts = parts.next().unwrap();
+        (pri, ts, parts.next().unwrap())
+    }
+
+    #[test]
+    fn syslog_udp() {
+        use std::net::UdpSocket;
+
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let drain = syslog::Syslog::udp(listener.local_addr().unwrap())
+            .unwrap()
+            .facility(syslog::Facility::Local0)
+            .app_name("my app")
+            .hostname(Some("web1".into()))
+            .sd_id("test@32473");
+        let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+        error!(log, #"db", "query failed"; "query" => "a[\"x\"]\\", "ms" => 1500);
+
+        let mut buf = [0; 1024];
+        let len = listener.recv(&mut buf).unwrap();
+        let (pri, ts, rest) =
+            split_syslog(std::str::from_utf8(&buf[..len]).unwrap());
+        assert_eq!(pri, "<131>1");
+        assert!(ts.ends_with('Z'), "{}", ts);
+        assert_eq!(
+            rest,
+            format!(
+                r#"web1 my_app {} db [test@32473 ms="1500" query="a[\"x\"\]\\" version="1.2"] query failed"#,
+                std::process::id()
+            )
+        );
+    }
+
+    #[test]
+    fn syslog_tcp_octet_counting() {
+        use std::io::Read;
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let drain = syslog::Syslog::tcp(listener.local_addr().unwrap())
+            .unwrap()
+            .app_name("app")
+            .hostname(None);
+        let (mut stream, _) = listener.accept().unwrap();
+        {
+            let log = Logger::root(drain.fuse(), o!());
+            warn!(log, "disk at {}%", 91; "mount" => "/var");
+            info!(log, "no kv");
+        }
+        let mut received = String::new();
+        stream.read_to_string(&mut received).unwrap();
+
+        let mut msgs = vec![];
+        let mut rest = received.as_str();
+        while !rest.is_empty() {
+            let space = rest.find(' ').unwrap();
+            let len: usize = rest[..space].parse().unwrap();
+            msgs.push(split_syslog(&rest[space + 1..space + 1 + len]));
+            rest = &rest[space + 1 + len..];
+        }
+        let pid = std::process::id();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs[0].0, "<12>1");
+        assert_eq!(
+            msgs[0].2,
+            format!(r#"- app {} - [slog@32473 mount="/var"] disk at 91%"#, pid)
+        );
+        assert_eq!(msgs[1].0, "<14>1");
+        assert_eq!(msgs[1].2, format!("- app {} - - no kv", pid));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn syslog_unix_datagram() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir()
+            .join(format!("slog-syslog-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+        let drain = syslog::Syslog::unix(&path).unwrap().app_name("app");
+        let log = Logger::root(drain.fuse(), o!());
+        crit!(log, "down");
+
+        let mut buf = [0; 1024];
+        let len = listener.recv(&mut buf).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (pri, _, rest) =
+            split_syslog(std::str::from_utf8(&buf[..len]).unwrap());
+        assert_eq!(pri, "<10>1");
+        assert!(rest.ends_with(" - - down"), "{}", rest);
+    }
+
+    #[test]
+    fn deflate_checksums() {
+        assert_eq!(deflate::crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(deflate::adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    /// Decompress a raw DEFLATE stream of blocks with fixed Huffman codes
+    fn inflate_fixed(data: &[u8]) -> Vec<u8> {
+        const LENGTH_BASE: [usize; 29] = [
+            3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43,
+            51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+        ];
+        const DIST_BASE: [usize; 30] = [
+            1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257,
+            385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289,
+            16385, 24577,
+        ];
+        let length_extra = |i: usize| if i < 8 || i == 28 { 0 } else { i / 4 - 1 };
+        let dist_extra = |i: usize| if i < 4 { 0 } else { i / 2 - 1 };
+
+        let mut pos = 0;
+        let bit = |pos: &mut usize| {
+            let bit = (data[*pos / 8] >> (*pos % 8)) & 1;
+            *pos += 1;
+            usize::from(bit)
+        };
+        let bits = |pos: &mut usize, n: usize| {
+            (0..n).fold(0, |acc, i| acc | bit(pos) << i)
+        };
+        let code = |pos: &mut usize, n: usize| {
+            (0..n).fold(0, |acc, _| acc << 1 | bit(pos))
+        };
+        let mut out = vec![];
+        loop {
+            let last = bit(&mut pos);
+            assert_eq!(bits(&mut pos, 2), 1, "not a fixed Huffman block");
+            loop {
+                let symbol = match code(&mut pos, 7) {
+                    code @ 0..=0x17 => code + 256,
+                    code => match code << 1 | bit(&mut pos) {
+                        code @ 0x30..=0xbf => code - 0x30,
+                        code @ 0xc0..=0xc7 => code - 0xc0 + 280,
+                        code => (code << 1 | bit(&mut pos)) - 0x190 + 144,
+                    },
+                };
+                if symbol < 256 {
+                    out.push(symbol as u8);
+                } else if symbol == 256 {
+                    break;
+                } else {
+                    let i = symbol - 257;
+                    let len = LENGTH_BASE[i] + bits(&mut pos, length_extra(i));
+                    let d = code(&mut pos, 5);
+                    let dist = DIST_BASE[d] + bits(&mut pos, dist_extra(d));
+                    for _ in 0..len {
+                        let byte = out[out.len() - dist];
+                        out.push(byte);
+                    }
+                }
+            }
+            if last == 1 {
+                return out;
+            }
+        }
+    }
+
+    #[test]
+    fn deflate_roundtrip() {
+        let mut data = Vec::new();
+        for i in 0..2000u32 {
+            data.extend_from_slice(format!("record {} of {}; ", i % 7, i).as_bytes());
+        }
+        data.extend((0..=255u8).cycle().take(1000));
+
+        let zlib = deflate::zlib(&data);
+        assert!(zlib.len() < data.len() / 2);
+        assert_eq!(&zlib[..2], [0x78, 0x01]);
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        assert_eq!(inflate_fixed(&zlib[2..zlib.len() - 4]), data);
+        assert_eq!(zlib[zlib.len() - 4..], deflate::adler32(&data).to_be_bytes());
+
+        let gzip = deflate::gzip(b"");
+        assert_eq!(&gzip[..3], [0x1f, 0x8b, 8]);
+        assert_eq!(inflate_fixed(&gzip[10..gzip.len() - 8]), b"");
+    }
+
+    #[test]
+    fn gelf_encoder() {
+        let encoder = gelf::Encoder::new().host("web1");
+        let values = o!("version" => "1.2", "user id" => "root", "line" => 0).into();
+        let line = line!() + 3;
+        let json = encoder
+            .encode(
+                &record!(Level::Warning, "", &format_args!("disk \"{}\"", "sda"), b!("free" => 0.5)),
+                &values,
+            )
+            .unwrap();
+
+        let ts_start = json.find(r#""timestamp":"#).unwrap();
+        let ts_end = json.find(r#","level""#).unwrap();
+        assert_eq!(
+            format!("{}{}", &json[..ts_start], &json[ts_end + 1..]),
+            format!(
+                concat!(
+                    r#"{{"version":"1.1","host":"web1","short_message":"disk \"sda\"","#,
+                    r#""level":4,"_file":"{}","_line":{},"_module":"{}","#,
+                    r#""_free":0.5,"_user_id":"root","_version":"1.2"}}"#
+                ),
+                file!(),
+                line,
+                module_path!()
+            )
+        );
+
+        let values = o!().into();
+        match encoder.encode(&record!(Level::Info, "", &format_args!("x"), b!("id" => 1)), &values) {
+            Err(gelf::GelfError::ReservedKey) => {}
+            res => panic!("unexpected {:?}", res),
+        }
+    }
+
+    #[test]
+    fn gelf_udp_chunks() {
+        use std::net::UdpSocket;
+
+        let listener = UdpSocket::bind("127.0.0.1:0").unwrap();
+        listener
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        let msg: String = (0..100).map(|i| format!("part {} ", i)).collect();
+
+        for &compression in &[
+            gelf::Compression::None,
+            gelf::Compression::Zlib,
+            gelf::Compression::Gzip,
+        ] {
+            let drain = gelf::Gelf::udp(addr)
+                .unwrap()
+                .host("web1")
+                .compression(compression)
+                .chunk_size(100);
+            let log = Logger::root(drain.fuse(), o!());
+            info!(log, "{}", msg);
+
+            let mut chunks: Vec<Vec<u8>> = vec![];
+            let mut buf = [0; 200];
+            loop {
+                let len = listener.recv(&mut buf).unwrap();
+                assert!(len <= 100);
+                assert_eq!(&buf[..2], [0x1e, 0x0f]);
+                if let Some(first) = chunks.first() {
+                    assert_eq!(first[2..10], buf[2..10], "message id");
+                }
+                chunks.push(buf[..len].to_vec());
+                if chunks.len() == usize::from(buf[11]) {
+                    break;
+                }
+            }
+            chunks.sort_by_key(|chunk| chunk[10]);
+            let payload: Vec<u8> =
+                chunks.iter().flat_map(|chunk| chunk[12..].to_vec()).collect();
+            let json = match compression {
+                gelf::Compression::None => payload,
+                gelf::Compression::Zlib => {
+                    inflate_fixed(&payload[2..payload.len() - 4])
+                }
+                gelf::Compression::Gzip => {
+                    inflate_fixed(&payload[10..payload.len() - 8])
+                }
+            };
+            let json = String::from_utf8(json).unwrap();
+            assert!(json.starts_with(r#"{"version":"1.1","host":"web1""#));
+            assert!(json.contains(&format!(r#""short_message":"{}""#, msg)));
+        }
+    }
+
+    fn decode_all(bytes: &[u8]) -> (Vec<binary::DecodedRecord>, bool) {
+        let mut decoder = binary::Decoder::new(bytes).unwrap();
+        let records = decoder.by_ref().map(|r| r.unwrap()).collect();
+        (records, decoder.truncated())
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        use binary::DecodedValue::Value;
+
+        let buf = SharedBuf::default();
+        let drain = binary::Binary::new(buf.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!("version" => "1.2"));
+        let line = line!() + 1;
+        info!(log, "values #{}", 1; "usize" => 1usize, "isize" => -1isize,
+            "bool" => true, "char" => 'é', "u8" => 8u8, "i8" => -8i8,
+            "u16" => 16u16, "i16" => -16i16, "u32" => 32u32, "i32" => -32i32,
+            "f32" => 0.5f32, "u64" => u64::MAX, "i64" => i64::MIN,
+            "f64" => f64::NEG_INFINITY, "str" => "a \"b\"", "unit" => (),
+            "none" => None::<u8>, "args" => %format_args!("{}-{}", 1, 2));
+        warn!(log, #"db", "static"; "u8" => 0u8);
+
+        let (records, truncated) = decode_all(&buf.0.lock().unwrap());
+        assert!(!truncated);
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!(record.level(), Level::Info);
+        assert_eq!(record.msg(), "values #1");
+        assert_eq!(record.file(), file!());
+        assert_eq!(record.line(), line);
+        assert_eq!(record.module(), module_path!());
+        assert_eq!(record.tag(), "");
+        let kv: Vec<_> =
+            record.kv().iter().map(|(k, v)| (k.as_str(), v.clone())).collect();
+        assert_eq!(
+            kv,
+            [
+                ("args", Value(OwnedValue::Arguments("1-2".into()))),
+                ("none", Value(OwnedValue::None)),
+                ("unit", Value(OwnedValue::Unit)),
+                ("str", Value(OwnedValue::Str("a \"b\"".into()))),
+                ("f64", Value(OwnedValue::F64(f64::NEG_INFINITY))),
+                ("i64", Value(OwnedValue::I64(i64::MIN))),
+                ("u64", Value(OwnedValue::U64(u64::MAX))),
+                ("f32", Value(OwnedValue::F32(0.5))),
+                ("i32", Value(OwnedValue::I32(-32))),
+                ("u32", Value(OwnedValue::U32(32))),
+                ("i16", Value(OwnedValue::I16(-16))),
+                ("u16", Value(OwnedValue::U16(16))),
+                ("i8", Value(OwnedValue::I8(-8))),
+                ("u8", Value(OwnedValue::U8(8))),
+                ("char", Value(OwnedValue::Char('é'))),
+                ("bool", Value(OwnedValue::Bool(true))),
+                ("isize", Value(OwnedValue::Isize(-1))),
+                ("usize", Value(OwnedValue::Usize(1))),
+                ("version", Value(OwnedValue::Str("1.2".into()))),
+            ]
+        );
+        let elapsed = record.time().elapsed().unwrap();
+        assert!(elapsed < std::time::Duration::from_secs(60));
+
+        let record = &records[1];
+        assert_eq!((record.level(), record.tag()), (Level::Warning, "db"));
+        let text = record.to_text();
+        assert!(text.ends_with("Z WARN static u8=0 version=1.2"), "{}", text);
+        let json = record.to_json();
+        assert!(json.starts_with(r#"{"ts":""#), "{}", json);
+        assert!(
+            json.ends_with(&format!(
+                r#"Z","level":"WARN","msg":"static","file":"{}","line":{},"module":"{}","tag":"db","u8":0,"version":"1.2"}}"#,
+                file!(),
+                line + 6,
+                module_path!()
+            )),
+            "{}",
+            json
+        );
+        let json = records[0].to_json();
+        assert!(json.contains(r#""str":"a \"b\"","f64":"-Infinity""#));
+        assert!(json.contains(r#""unit":null,"#));
+    }
+
+    #[test]
+    fn binary_interns_strings() {
+        let buf = SharedBuf::default();
+        let drain = binary::Binary::new(buf.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        for i in 0..3 {
+            info!(log, "static message"; "counter" => i);
+            info!(log, "message {}", i; "counter" => i);
+        }
+
+        let bytes = buf.0.lock().unwrap().clone();
+        let count = |s: &str| {
+            bytes.windows(s.len()).filter(|w| *w == s.as_bytes()).count()
+        };
+        assert_eq!(count(file!()), 1);
+        assert_eq!(count(module_path!()), 1);
+        assert_eq!(count("counter"), 1);
+        // messages are never interned
+        assert_eq!(count("static message"), 3);
+        assert_eq!(count("message "), 3);
+
+        let (records, _) = decode_all(&bytes);
+        let msgs: Vec<_> = records.iter().map(|r| r.msg()).collect();
+        assert_eq!(
+            msgs,
+            [
+                "static message",
+                "message 0",
+                "static message",
+                "message 1",
+                "static message",
+                "message 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn binary_truncated_record() {
+        let buf = SharedBuf::default();
+        let drain = binary::Binary::new(buf.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "first"; "n" => 1);
+        let complete = buf.0.lock().unwrap().len();
+        let n = 2;
+        info!(log, "second {}", n; "new key" => n);
+        let bytes = buf.0.lock().unwrap().clone();
+
+        // cut everywhere in the last write: in the string entry of `new key`
+        // (length, checksum, kind, string), after it, and in the record entry
+        let key_entry_end = complete + 8 + 1 + "new key".len();
+        for len in complete..bytes.len() {
+            let (records, truncated) = decode_all(&bytes[..len]);
+            assert_eq!(records.len(), 1, "cut at {}", len);
+            assert_eq!(records[0].msg(), "first");
+            let between_entries = len == complete || len == key_entry_end;
+            assert_eq!(truncated, !between_entries, "cut at {}", len);
+        }
+        let (records, truncated) = decode_all(&bytes);
+        assert_eq!((records.len(), truncated), (2, false));
+
+        match binary::Decoder::new(&b"SLOGBIN\x02"[..]) {
+            Err(binary::DecodeError::UnsupportedVersion(2)) => {}
+            res => panic!("unexpected {:?}", res.err()),
+        }
+        match binary::Decoder::new(&b"{\"ts\":"[..]) {
+            Err(binary::DecodeError::NotBinaryLog) => {}
+            res => panic!("unexpected {:?}", res.err()),
+        }
+        // type of the value of `n`, followed by 4 bytes of the `i32`
+        let mut corrupt = bytes.clone();
+        corrupt[complete - 5] = 0xff;
+        let mut decoder = binary::Decoder::new(&corrupt[..]).unwrap();
+        match decoder.next() {
+            Some(Err(binary::DecodeError::Malformed)) => {}
+            res => panic!("unexpected {:?}", res),
+        }
+        assert!(decoder.next().is_none());
+    }
+
+    #[test]
+    fn binary_magic_in_strings() {
+        let buf = SharedBuf::default();
+        let drain = binary::Binary::new(buf.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "reading SLOGBINARY files"; "SLOGBIN key" => 1);
+        info!(log, "reading {}", "SLOGBIN\x01"; "SLOGBIN\x01" => 2);
+        info!(log, "done");
+
+        let (records, truncated) = decode_all(&buf.0.lock().unwrap());
+        let msgs: Vec<_> = records.iter().map(|r| r.msg()).collect();
+        assert_eq!(
+            msgs,
+            ["reading SLOGBINARY files", "reading SLOGBIN\x01", "done"]
+        );
+        assert!(records[0].to_json().contains("\"SLOGBIN key\":1"));
+        assert!(records[1].to_json().contains("\"SLOGBIN\\u0001\":2"));
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn binary_appended_sessions() {
+        let first = SharedBuf::default();
+        let drain = binary::Binary::new(first.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "first"; "n" => 1);
+        let complete = first.0.lock().unwrap().len();
+        let n = 2;
+        info!(log, "second {}", n; "new key" => n);
+        let first = first.0.lock().unwrap().clone();
+
+        let second = SharedBuf::default();
+        let drain = binary::Binary::new(second.clone()).unwrap();
+        let log = Logger::root(drain.fuse(), o!());
+        info!(log, "third"; "n" => 3);
+        // a complete entry is kept, even if it looks like a new session
+        info!(log, "fourth"; "bytes" => "SLOGBIN\x01");
+        let second = second.0.lock().unwrap().clone();
+
+        let (records, truncated) = decode_all(&[&first[..], &second[..]].concat());
+        let msgs: Vec<_> = records.iter().map(|r| r.msg()).collect();
+        assert_eq!(msgs, ["first", "second 2", "third", "fourth"]);
+        assert!(records[2].to_json().contains("\"n\":3"));
+        assert!(!truncated);
+
+        // restart after a crash anywhere in the last write of the first
+        // session
+        let key_entry_end = complete + 8 + 1 + "new key".len();
+        for len in complete..first.len() {
+            let bytes = [&first[..len], &second[..]].concat();
+            let (records, truncated) = decode_all(&bytes);
+            let msgs: Vec<_> = records.iter().map(|r| r.msg()).collect();
+            assert_eq!(msgs, ["first", "third", "fourth"], "cut at {}", len);
+            assert_eq!(records[1].kv().len(), 1, "cut at {}", len);
+            let between_entries = len == complete || len == key_entry_end;
+            assert_eq!(truncated, !between_entries, "cut at {}", len);
+        }
+    }
 }
 
 #[test]